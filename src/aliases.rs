@@ -0,0 +1,153 @@
+use regex::Regex;
+
+use crate::text::normalize_pattern;
+
+/// Which part of a track an alias rewrites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasField {
+    Artist,
+    Track,
+}
+
+impl AliasField {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AliasField::Artist => "artist",
+            AliasField::Track => "track",
+        }
+    }
+}
+
+/// One `artist`/`track` line of the `aliases` block as written in the config.
+#[derive(Debug, Clone)]
+pub struct AliasSpec {
+    pub field: AliasField,
+    pub pattern: String,
+    pub replacement: String,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    /// Whole value, compared case-insensitively (stored lowercased)
+    Exact(String),
+    /// `/.../` patterns; the replacement may use `$1` or `${name}`
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+struct Alias {
+    field: AliasField,
+    pattern: String,
+    matcher: Matcher,
+    replacement: String,
+}
+
+/// Ordered alias table. For each field the first matching alias wins.
+#[derive(Debug, Default)]
+pub struct Aliases {
+    aliases: Vec<Alias>,
+}
+
+impl Aliases {
+    /// Compile `specs` in order. Every invalid regex is reported, not just the first.
+    pub fn from_specs(specs: &[AliasSpec]) -> Result<Aliases, Vec<String>> {
+        let mut aliases = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs {
+            let is_regex = spec.pattern.len() >= 2 && spec.pattern.starts_with('/') && spec.pattern.ends_with('/');
+            let matcher = if is_regex {
+                match Regex::new(&normalize_pattern(&spec.pattern)) {
+                    Ok(re) => Matcher::Regex(re),
+                    Err(e) => {
+                        errors.push(format!("{} alias {:?}: invalid regex: {}", spec.field.as_str(), spec.pattern, e));
+                        continue;
+                    }
+                }
+            } else {
+                Matcher::Exact(spec.pattern.trim().to_lowercase())
+            };
+            aliases.push(Alias { field: spec.field, pattern: spec.pattern.clone(), matcher, replacement: spec.replacement.clone() });
+        }
+        if errors.is_empty() { Ok(Aliases { aliases }) } else { Err(errors) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// Rewrite `value` with the first alias for `field` that matches it. Returns the new value and the
+    /// pattern of the alias that was used, or `None` when nothing matched or the value didn't change.
+    pub fn apply(&self, field: AliasField, value: &str) -> Option<(String, &str)> {
+        let alias = self.aliases.iter().filter(|a| a.field == field).find(|a| match &a.matcher {
+            Matcher::Exact(s) => value.trim().to_lowercase() == *s,
+            Matcher::Regex(re) => re.is_match(value),
+        })?;
+        let replaced = match &alias.matcher {
+            Matcher::Exact(_) => alias.replacement.clone(),
+            Matcher::Regex(re) => re.replace_all(value, alias.replacement.as_str()).into_owned(),
+        };
+        (replaced != value).then_some((replaced, alias.pattern.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(specs: &[(AliasField, &str, &str)]) -> Aliases {
+        let specs: Vec<AliasSpec> = specs
+            .iter()
+            .map(|(field, pattern, replacement)| AliasSpec {
+                field: *field,
+                pattern: pattern.to_string(),
+                replacement: replacement.to_string(),
+            })
+            .collect();
+        Aliases::from_specs(&specs).unwrap()
+    }
+
+    #[test]
+    fn exact_aliases_match_the_whole_value_ignoring_case() {
+        let a = aliases(&[(AliasField::Artist, "Beyonce", "Beyoncé")]);
+        assert_eq!(a.apply(AliasField::Artist, "beyonce "), Some(("Beyoncé".to_string(), "Beyonce")));
+        assert_eq!(a.apply(AliasField::Artist, "Beyonce Knowles"), None);
+        // Other fields are not touched
+        assert_eq!(a.apply(AliasField::Track, "Beyonce"), None);
+        // Already correct: nothing to report
+        assert_eq!(a.apply(AliasField::Artist, "Beyoncé"), None);
+    }
+
+    #[test]
+    fn regex_aliases_replace_matches_with_captures() {
+        let a = aliases(&[
+            (AliasField::Track, r"/^(.+) \(Album Version\)$/", "$1"),
+            (AliasField::Artist, r"/(?i)^the (?P<band>.+)$/", "${band}, The"),
+        ]);
+        assert_eq!(a.apply(AliasField::Track, "Creep (Album Version)").map(|r| r.0), Some("Creep".to_string()));
+        assert_eq!(a.apply(AliasField::Artist, "the Beatles").map(|r| r.0), Some("Beatles, The".to_string()));
+        assert_eq!(a.apply(AliasField::Track, "Creep"), None);
+    }
+
+    #[test]
+    fn first_matching_alias_wins() {
+        let a = aliases(&[
+            (AliasField::Artist, "/^Guns.*/", "GNR"),
+            (AliasField::Artist, "Guns N' Roses", "Guns N’ Roses"),
+            (AliasField::Artist, "/Roses/", "Flowers"),
+        ]);
+        assert_eq!(a.apply(AliasField::Artist, "Guns N' Roses"), Some(("GNR".to_string(), "/^Guns.*/")));
+        assert_eq!(a.apply(AliasField::Artist, "Roses"), Some(("Flowers".to_string(), "/Roses/")));
+        assert_eq!(a.len(), 3);
+    }
+
+    #[test]
+    fn every_invalid_regex_is_reported() {
+        let specs: Vec<AliasSpec> = ["/(/", "/[/", "fine"]
+            .iter()
+            .map(|p| AliasSpec { field: AliasField::Track, pattern: p.to_string(), replacement: String::new() })
+            .collect();
+        let errors = Aliases::from_specs(&specs).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("track alias \"/(/\": invalid regex"));
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::discord::is_snowflake;
+use crate::http_template::send_template;
+
+/// Discord's limit for an application's description, which is shown as the bot's About Me.
+pub const BOT_ABOUT_LIMIT: usize = 400;
+
+/// Accept tokens pasted with or without the scheme; the templates add `Bot ` themselves.
+pub fn normalize_bot_token(token: &str) -> String {
+    let t = token.trim();
+    t.strip_prefix("Bot ").unwrap_or(t).trim().to_string()
+}
+
+/// Post a message (content or embeds, as built by `webhook::build_payload`) to a channel as the bot.
+pub async fn post_channel_message(bot_token: &str, channel_id: &str, payload: &Value, debug: bool) -> Result<()> {
+    if !is_snowflake(channel_id) {
+        return Err(anyhow!("Invalid channel id '{}': expected the numeric channel id", channel_id));
+    }
+    let mut payload = payload.clone();
+    // Bots always post under their own name; `username` is a webhook-only field
+    if let Some(obj) = payload.as_object_mut() {
+        obj.remove("username");
+    }
+    let vars = [
+        ("DISCORD_BOT_TOKEN", normalize_bot_token(bot_token)),
+        ("CHANNEL_ID", channel_id.to_string()),
+        ("MESSAGE_PAYLOAD", payload.to_string()),
+    ];
+    send_template("discord_bot_post_message.http", &vars, debug).await?;
+    Ok(())
+}
+
+/// Replace the bot's own About Me (its application description).
+pub async fn update_bot_about(bot_token: &str, about: &str, debug: bool) -> Result<()> {
+    let vars = [
+        ("DISCORD_BOT_TOKEN", normalize_bot_token(bot_token)),
+        ("ABOUT_PAYLOAD", json!({ "description": about }).to_string()),
+    ];
+    send_template("discord_bot_patch_about.http", &vars, debug).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bot_token_prefix_is_optional() {
+        assert_eq!(normalize_bot_token("abc.def"), "abc.def");
+        assert_eq!(normalize_bot_token("Bot abc.def"), "abc.def");
+        assert_eq!(normalize_bot_token("  Bot   abc.def \n"), "abc.def");
+        // Only the exact scheme is stripped; a token that merely starts with "Bot" is kept whole
+        assert_eq!(normalize_bot_token("Botabc"), "Botabc");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_channel_id_that_is_not_a_snowflake() {
+        let err = post_channel_message("abc", "../users/@me", &json!({ "content": "hi" }), false).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid channel id"));
+    }
+}
@@ -1,4 +1,4 @@
-﻿use clap::{ArgGroup, Parser, ValueEnum};
+﻿use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Period {
@@ -23,19 +23,77 @@ impl Period {
     }
 }
 
+/// How to shrink a Discord bio that exceeds the length limit. Strategies are tried in the order given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FitStrategy {
+    /// Drop tracks from the end of the list (always keeps at least one)
+    Drop,
+    /// Shorten the longest track titles, marking the cut with an ellipsis
+    Truncate,
+    /// Switch every entry to the shorter `--fallback-format`
+    Fallback,
+}
+
+/// What to do when the Discord bio was edited elsewhere between fetching it and sending the update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Leave the bio untouched and report the conflict
+    Abort,
+    /// Apply the same replacement to the latest bio instead
+    Reapply,
+}
+
+/// Which Discord profile field an update or dry-run applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiscordTarget {
+    /// The About Me section; only the part matched by --discord-bio-regex is replaced
+    Bio,
+    /// Custom status text, rendered from --status-format with the first selected track
+    Status,
+    /// Pronouns, rendered from --pronouns-format with the first selected track
+    Pronouns,
+    /// Server-specific bio in every --guild-id, replaced with the full rendered list
+    GuildBio,
+}
+
+/// How the fetched list (with --query) and the final selection are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable listing and the rendered bio line
+    Text,
+    /// JSON object with username, period, tracks (and the rendered list after selection)
+    Json,
+    /// CSV with a header row: rank, artist, track, playcount, url, featured, artists
+    Csv,
+    /// Markdown table
+    Markdown,
+    /// HTML table
+    Html,
+}
+
 #[derive(Parser, Debug)]
-#[command(name = "topsongs", version, about = "Fetch Last.fm top tracks and format them for your Discord bio", long_about = None)]
+#[command(
+    name = "topsongs",
+    version,
+    about = "Fetch Last.fm top tracks and format them for your Discord bio",
+    long_about = None,
+    after_help = "On/off flags such as --copy or --debug have a --no-<flag> form (--no-copy, --no-debug) that turns off a \
+                  setting switched on in the config or environment. --merge and --confirm undo merge #false and discord_confirm #false."
+)]
 #[command(group(
     ArgGroup::new("auth")
         .args(["api_key"]) // keeping for future expansion
         .multiple(true)
 ))]
 pub struct Cli { 
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Generate an example KDL config (topsongs.config.kdl) in the current directory and exit
     #[arg(short = 'G', long)]
     pub generate_config: bool, 
 
-    /// Generate barebones .http templates: use without a value to create all missing defaults, or pass one of [lastfm_top_tracks | discord_get_me | discord_patch_bio] to create a specific file if missing; then exit
+    /// Generate barebones .http templates: use without a value to create all missing defaults, or pass a template name (e.g. lastfm_top_tracks, discord_patch_bio, discord_patch_status) to create a specific file if missing; then exit
     #[arg(long = "generate-http", value_name = "TEMPLATE", num_args = 0..=1, default_missing_value = "ALL")]
     pub generate_http: Option<String>,
 
@@ -51,64 +109,370 @@ pub struct Cli {
     #[arg(short, long, value_enum)]
     pub period: Option<Period>,
 
-    /// Number of top tracks to fetch/display
-    #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u32))]
-    pub limit: u32,
+    /// Number of top tracks to fetch/display. Defaults to 10
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u32))]
+    pub limit: Option<u32>,
 
     /// Show Last.fm results only and exit (skip the selection and rendering process)
     #[arg(short = 'Q', long = "query")]
     pub query: bool,
 
+    /// Show the fetched tracks as an aligned table (columns measured in display width, so CJK and emoji line up)
+    #[arg(long, overrides_with = "no_table")]
+    pub table: bool,
+    #[arg(long, overrides_with = "table", hide = true)]
+    pub no_table: bool,
+
+    /// Output format for the --query listing and the final list. Non-text formats print only the
+    /// formatted data on stdout; progress messages go to stderr
+    #[arg(long, value_enum)]
+    pub output_format: Option<OutputFormat>,
+
     /// Automatically include the top N tracks (skips interactive selection). If omitted, you'll be prompted to choose interactively.
     #[arg(short, long, value_parser = clap::value_parser!(usize))]
     pub select: Option<usize>,
 
-    /// Format template for each entry. Tokens: {artist}, {track}, {playcount}
-    #[arg(short = 'f', long, default_value = "  - {artist} - {track}")]
-    pub format: String,
+    /// Format template for each entry. Tokens: {rank}, {artist}, {track}, {playcount};
+    /// filters like {track|upper}, {artist|truncate:20}, {playcount|lpad:5}, {track|romanize}; conditionals like
+    /// {?playcount>100}🔥{/}; {{ and }} for literal braces. Defaults to "  - {artist} - {track}"
+    #[arg(short = 'f', long)]
+    pub format: Option<String>,
 
-    /// Joiner between entries
-    #[arg(short = 'j', long, default_value = "\n")]
-    pub join: String,
+    /// Joiner between entries (a template; {count} is the number of entries). Defaults to a newline
+    #[arg(short = 'j', long)]
+    pub join: Option<String>,
 
-    /// String to place before the joined entries (e.g. "**Song Recs**:\n")
-    #[arg(long, default_value = "")]
-    pub prefix: String,
+    /// String to place before the joined entries (e.g. "**Top {count}**:\n")
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// String to place after the joined entries (same template syntax as --prefix)
+    #[arg(long)]
+    pub suffix: Option<String>,
+
+    /// Whole-list template replacing prefix/join/suffix, e.g. "Top {count}:\n{#for tracks}{rank}. {entry}\n{/}".
+    /// Loop variables: {entry}, {loop.index}, {loop.first}, {loop.last}; list variables: {count},
+    /// {total_plays}, {period}, {username}, {date}
+    #[arg(long, conflicts_with = "list_template_file")]
+    pub list_template: Option<String>,
+
+    /// Read the whole-list template from a file (see --list-template)
+    #[arg(long)]
+    pub list_template_file: Option<String>,
 
-    /// String to place after the joined entries
-    #[arg(long, default_value = "")]
-    pub suffix: String,
+    /// Escape Markdown characters (* _ ~ ` | \) in track/artist values so Discord shows them literally.
+    /// Filters like {track|bold}, {artist|spoiler} and {url|link:track} always escape what they wrap
+    #[arg(long, overrides_with = "no_discord_markdown")]
+    pub discord_markdown: bool,
+    #[arg(long, overrides_with = "discord_markdown", hide = true)]
+    pub no_discord_markdown: bool,
+
+    /// Use a named preset (format/join/prefix/suffix bundle) from the config or the built-ins
+    /// ("discord", "markdown", "plain"). Explicit --format/--join/--prefix/--suffix still take precedence
+    #[arg(long)]
+    pub preset: Option<String>,
 
     /// If set, remove trailing featured-artist annotations like "(feat. ...)" or "- ft. ..." from track titles, then trim spaces
-    #[arg(short = 't', long)]
+    #[arg(short = 't', long, overrides_with = "no_strip_feat")]
     pub strip_feat: bool,
+    #[arg(long, overrides_with = "strip_feat", hide = true)]
+    pub no_strip_feat: bool,
+
+    /// Move featured artists from the title into the artist: "Song (feat. X)" by "A" becomes "Song" by "A & X".
+    /// Without this flag the featured artists are still available as {featured} and {artists}
+    #[arg(long, overrides_with = "no_move_feat")]
+    pub move_feat: bool,
+    #[arg(long, overrides_with = "move_feat", hide = true)]
+    pub no_move_feat: bool,
+
+    /// Keep near-identical entries separate instead of merging them (same artist and title after cleanup,
+    /// ignoring case, punctuation and remaster/edit/edition tags; merged entries sum their playcounts)
+    #[arg(long, overrides_with = "merge")]
+    pub no_merge: bool,
+    #[arg(long, overrides_with = "no_merge", hide = true)]
+    pub merge: bool,
+
+    /// Only show tracks by these artists (repeatable). A plain name matches exactly (ignoring case),
+    /// `*`/`?` make a glob, /.../ is a regex. Featured artists count too
+    #[arg(long, value_name = "PATTERN")]
+    pub include_artist: Vec<String>,
 
-    /// Optional custom regex to strip from track titles (Rust regex). If surrounded by slashes, they will be stripped. Used only if --strip-feat is set
+    /// Only show tracks whose title matches (repeatable; same pattern syntax as --include-artist)
+    #[arg(long, value_name = "PATTERN")]
+    pub include_track: Vec<String>,
+
+    /// Hide tracks by these artists (repeatable; same pattern syntax as --include-artist).
+    /// More tracks are fetched so the list still has --limit entries
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude_artist: Vec<String>,
+
+    /// Hide tracks whose title matches (repeatable; same pattern syntax as --include-artist)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude_track: Vec<String>,
+
+    /// Print which entries of the config's `aliases` block rewrote an artist or title, before the listing
+    #[arg(long)]
+    pub show_aliases_applied: bool,
+
+    /// Transliterate Cyrillic, Greek, Japanese kana and Hangul in titles and artists to Latin letters
+    /// (per track; use the {track|romanize} filter for single tokens). Official names come from `romanized_names` in the config
+    #[arg(long, overrides_with = "no_transliterate")]
+    pub transliterate: bool,
+    #[arg(long, overrides_with = "transliterate", hide = true)]
+    pub no_transliterate: bool,
+
+    /// Separator used to join artists in {featured}, {artists} and --move-feat (default " & ")
+    #[arg(long)]
+    pub featured_separator: Option<String>,
+
+    /// Optional custom regex to strip from track titles (Rust regex). If surrounded by slashes, they will be stripped. Used only if --strip-feat is set,
+    /// or as the pattern of the `feat` rule in a config `cleanup` block
     #[arg(long)]
     pub strip_feat_regex: Option<String>,
 
     /// Copy the generated bio string to clipboard (Windows)
-    #[arg(short = 'c', long)]
+    #[arg(short = 'c', long, overrides_with = "no_copy")]
     pub copy: bool,
+    #[arg(long, overrides_with = "copy", hide = true)]
+    pub no_copy: bool,
 
     /// Discord user token; used for Discord operations when enabled via --update-discord or --discord-dry-run (or set DISCORD_TOKEN env var)
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub discord_token: Option<String>,
 
     /// Regex to locate the section of your bio to replace (use Rust regex syntax). If surrounded by slashes, they will be stripped.
-    #[arg(long, default_value = r"/\*\*[\w ]+\*\*:?\r?(\n[ \w-]+)+\n/")]
-    pub discord_bio_regex: String,
+    /// Defaults to a bold heading followed by list lines
+    #[arg(long)]
+    pub discord_bio_regex: Option<String>,
+
+    /// Maximum Discord bio length, counted the way Discord does (Unicode code points, CRLF as one). Defaults to 190
+    #[arg(long, value_parser = clap::value_parser!(usize))]
+    pub bio_limit: Option<usize>,
+
+    /// Auto-fit strategies used when the new bio is over the limit, tried in order (comma-separated): drop, truncate, fallback
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub bio_fit: Vec<FitStrategy>,
+
+    /// Shorter per-entry format used by the `fallback` auto-fit strategy (same tokens as --format)
+    #[arg(long)]
+    pub fallback_format: Option<String>,
 
     /// Perform Discord operations (fetch/preview/update). If not set, no Discord calls will be made even if DISCORD_TOKEN is present.
-    #[arg(short = 'U', long)]
+    #[arg(short = 'U', long, overrides_with = "no_update_discord")]
     pub update_discord: bool,
+    #[arg(long, overrides_with = "update_discord", hide = true)]
+    pub no_update_discord: bool,
 
     /// Dry-run Discord changes: fetch current bio and show the replacement result, but do not PATCH.
-    #[arg(short = 'r', long)]
+    #[arg(short = 'r', long, global = true, overrides_with = "no_discord_dry_run")]
     pub discord_dry_run: bool,
+    #[arg(long, overrides_with = "discord_dry_run", hide = true, global = true)]
+    pub no_discord_dry_run: bool,
+
+    /// Discord profile fields to update (comma-separated): bio, status, pronouns, guild-bio. Defaults to bio.
+    /// Only the bio is backed up; status, pronouns and server bios are overwritten without a record
+    #[arg(long = "discord-target", value_enum, value_delimiter = ',')]
+    pub discord_targets: Vec<DiscordTarget>,
+
+    /// Custom status template, rendered with the first selected track. Tokens: {artist}, {track}, {playcount}
+    #[arg(long)]
+    pub status_format: Option<String>,
+
+    /// Unicode emoji shown next to the custom status
+    #[arg(long)]
+    pub status_emoji: Option<String>,
+
+    /// Clear the custom status after this long (e.g. 30m, 4h, 1d) or at an RFC 3339 time. If omitted, the status does not expire
+    #[arg(long)]
+    pub status_expires: Option<String>,
+
+    /// Pronouns template, rendered with the first selected track (Discord allows 40 characters)
+    #[arg(long)]
+    pub pronouns_format: Option<String>,
+
+    /// Server (guild) id whose server-specific bio the guild-bio target updates; repeat for several servers
+    #[arg(long = "guild-id")]
+    pub guild_ids: Vec<String>,
+
+    /// Post the rendered tracks to a Discord channel webhook (honors --discord-dry-run)
+    #[arg(long, overrides_with = "no_webhook")]
+    pub webhook: bool,
+    #[arg(long, overrides_with = "webhook", hide = true)]
+    pub no_webhook: bool,
+
+    /// Discord webhook URL to post to (or set DISCORD_WEBHOOK_URL env var)
+    #[arg(long)]
+    pub webhook_url: Option<String>,
+
+    /// Post a rich embed (rank, title, artist, playcount and Last.fm link) instead of the plain rendered list (also applies to --bot-post)
+    #[arg(long, overrides_with = "no_webhook_embed")]
+    pub webhook_embed: bool,
+    #[arg(long, overrides_with = "webhook_embed", hide = true)]
+    pub no_webhook_embed: bool,
+
+    /// Display name to post the webhook message under
+    #[arg(long)]
+    pub webhook_username: Option<String>,
+
+    /// Discord bot token for bot/application operations (or set DISCORD_BOT_TOKEN env var). Never your user token
+    #[arg(long)]
+    pub bot_token: Option<String>,
+
+    /// Channel id the bot posts to with --bot-post
+    #[arg(long)]
+    pub bot_channel: Option<String>,
+
+    /// Post the rendered tracks to --bot-channel as the bot (honors --discord-dry-run)
+    #[arg(long, overrides_with = "no_bot_post")]
+    pub bot_post: bool,
+    #[arg(long, overrides_with = "bot_post", hide = true)]
+    pub no_bot_post: bool,
+
+    /// Replace the bot's own About Me with the rendered list (honors --discord-dry-run)
+    #[arg(long, overrides_with = "no_bot_about")]
+    pub bot_about: bool,
+    #[arg(long, overrides_with = "bot_about", hide = true)]
+    pub no_bot_about: bool,
+
+    /// Show the selected tracks as Discord Rich Presence via the local Discord client, rotating on an interval until Ctrl+C
+    #[arg(long)]
+    pub presence: bool,
+
+    /// Discord application id used for Rich Presence (create one in the Discord developer portal)
+    #[arg(long)]
+    pub presence_client_id: Option<String>,
+
+    /// Rich Presence details line. Tokens: {artist}, {track}, {playcount}. Defaults to "{track}"
+    #[arg(long)]
+    pub presence_details: Option<String>,
+
+    /// Rich Presence state line. Tokens: {artist}, {track}, {playcount}. Defaults to "by {artist}"
+    #[arg(long)]
+    pub presence_state: Option<String>,
+
+    /// Seconds between Rich Presence updates (minimum 15, Discord's rate limit). Defaults to 60
+    #[arg(long, value_parser = clap::value_parser!(u64))]
+    pub presence_interval: Option<u64>,
+
+    /// What to do if the bio changed in Discord between fetching it and sending the update (default: abort)
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Skip the diff confirmation prompt before a real --update-discord (needed when not running in a terminal)
+    #[arg(short = 'y', long, global = true, overrides_with = "confirm")]
+    pub yes: bool,
+    #[arg(long, global = true, overrides_with = "yes", hide = true)]
+    pub confirm: bool,
+
+    /// Apply a `profile "name" { ... }` block from the config on top of its top-level settings (or set
+    /// TOPSONGS_PROFILE)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
 
     /// Enable verbose logging: prints HTTP request details and response statuses (and bodies on errors)
-    #[arg(short = 'd', long)]
+    #[arg(short = 'd', long, global = true, overrides_with = "no_debug")]
     pub debug: bool,
+    #[arg(long, overrides_with = "debug", hide = true, global = true)]
+    pub no_debug: bool,
+
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect and roll back Discord bio changes made by topsongs
+    Discord {
+        #[command(subcommand)]
+        action: DiscordCommand,
+    },
+    /// Manage the passphrase-encrypted secret store (tokens and API keys kept out of the config file)
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommand,
+    },
+    /// Inspect the title cleanup rules and transliteration
+    Text {
+        #[command(subcommand)]
+        action: TextCommand,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print every effective setting after layering defaults, the config file, TOPSONGS_* environment
+    /// variables and the command line
+    Show {
+        /// Also print where each value came from
+        #[arg(long)]
+        origin: bool,
+    },
+    /// List the profiles defined in the config file and the keys each one sets
+    Profiles,
+    /// Check the config file for syntax errors, unknown keys and invalid values; exits with status 1 if
+    /// anything is wrong
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretCommand {
+    /// Store a secret (prompts for the value if omitted, which keeps it out of shell history)
+    Set {
+        name: SecretName,
+        value: Option<String>,
+    },
+    /// Print a stored secret
+    Get { name: SecretName },
+    /// Delete a stored secret
+    Remove { name: SecretName },
+}
+
+/// Secrets the encrypted store can hold, named after the config keys they replace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SecretName {
+    #[value(name = "discord_token")] DiscordToken,
+    #[value(name = "api_key")] ApiKey,
+    #[value(name = "discord_bot_token")] DiscordBotToken,
+    #[value(name = "webhook_url")] WebhookUrl,
+}
+
+impl SecretName {
+    pub fn as_key(&self) -> &'static str {
+        match self {
+            SecretName::DiscordToken => "discord_token",
+            SecretName::ApiKey => "api_key",
+            SecretName::DiscordBotToken => "discord_bot_token",
+            SecretName::WebhookUrl => "webhook_url",
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DiscordCommand {
+    /// List saved bio backups (oldest first)
+    History,
+    /// Restore the most recent backup, i.e. the bio as it was before the last update. Running it again reverts the undo.
+    Undo,
+    /// Restore the backup with the given id (see `topsongs discord history`)
+    Restore {
+        /// Backup id as shown by `topsongs discord history`
+        id: u32,
+    },
+}
 
+#[derive(Subcommand, Debug)]
+pub enum TextCommand {
+    /// Run a title through the cleanup rules and show what each rule changed
+    Test {
+        /// The track title to clean up
+        title: String,
+    },
+    /// Show how text is transliterated by --transliterate and the `romanize` filter
+    Romanize {
+        /// The title or artist to transliterate
+        text: String,
+    },
 }
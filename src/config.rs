@@ -1,5 +1,10 @@
 ﻿use std::fs;
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use crate::aliases::{AliasField, AliasSpec};
+use crate::filters::{FilterAction, FilterField, FilterSpec};
+use crate::text::RuleSpec;
+use std::path::PathBuf;
 
 #[derive(Debug, Default, Clone)]
 pub struct Config {
@@ -12,27 +17,235 @@ pub struct Config {
     pub join: Option<String>,
     pub prefix: Option<String>,
     pub suffix: Option<String>,
+    pub list_template: Option<String>,
+    pub list_template_file: Option<String>,
+    pub discord_markdown: Option<bool>,
     pub strip_feat: Option<bool>,
     pub strip_feat_regex: Option<String>,
+    /// Ordered title cleanup rules from the `cleanup { rule ... }` block; replaces strip_feat when present
+    pub cleanup: Option<Vec<RuleSpec>>,
+    /// Artist/title corrections from the `aliases` block, in order
+    pub aliases: Vec<AliasSpec>,
+    /// Include/exclude rules from the `filters` block; --include-*/--exclude-* flags add to these
+    pub filters: Vec<FilterSpec>,
+    pub move_feat: Option<bool>,
+    pub merge: Option<bool>,
+    pub transliterate: Option<bool>,
+    /// Official Latin spellings used by transliteration instead of the letter-by-letter result
+    pub romanized_names: Vec<(String, String)>,
+    pub featured_separator: Option<String>,
     pub copy: Option<bool>,
+    pub output_format: Option<String>,
+    pub table: Option<bool>,
     pub discord_token: Option<String>,
+    pub discord_token_file: Option<String>,
+    pub discord_token_command: Option<String>,
+    pub api_key_file: Option<String>,
+    pub api_key_command: Option<String>,
     pub discord_bio_regex: Option<String>,
+    pub discord_bio_limit: Option<usize>,
+    pub bio_fit: Option<String>,
+    pub fallback_format: Option<String>,
     pub update_discord: Option<bool>,
     pub discord_dry_run: Option<bool>,
+    pub discord_confirm: Option<bool>,
+    pub discord_on_conflict: Option<String>,
+    pub discord_targets: Option<String>,
+    pub status_format: Option<String>,
+    pub status_emoji: Option<String>,
+    pub status_expires: Option<String>,
+    pub pronouns_format: Option<String>,
+    pub discord_guild_ids: Option<Vec<String>>,
+    pub webhook: Option<bool>,
+    pub webhook_url: Option<String>,
+    pub webhook_embed: Option<bool>,
+    pub webhook_username: Option<String>,
+    pub presence_client_id: Option<String>,
+    pub presence_details: Option<String>,
+    pub presence_state: Option<String>,
+    pub presence_interval: Option<u64>,
+    pub bot: BotConfig,
+    /// `preset "name" { ... }` blocks, keyed by name
+    pub presets: BTreeMap<String, Preset>,
+    pub default_preset: Option<String>,
     pub debug: Option<bool>,
+    /// Every `profile "name" { ... }` block in the file, in order
+    pub profiles: Vec<ProfileSummary>,
+    /// The profile applied on top of the top-level settings, if one was selected
+    pub profile: Option<String>,
+    /// Keys set by the applied profile (`discord_bot.channel_id` for fields of the bot block)
+    pub profile_keys: Vec<String>,
+}
+
+/// A `profile` block as listed by `topsongs config profiles`.
+#[derive(Debug, Clone)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub username: Option<String>,
+    /// Keys the profile sets, in the same form as `Config::profile_keys`
+    pub keys: Vec<String>,
+}
+
+/// A named bundle of rendering settings, selected with `--preset` or `default_preset`.
+/// Unset fields use the built-in defaults, not the top-level `format`/`join`/`prefix`/`suffix`.
+#[derive(Debug, Default, Clone)]
+pub struct Preset {
+    pub format: Option<String>,
+    pub join: Option<String>,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    pub list_template: Option<String>,
+}
+
+/// Settings for the `discord_bot { ... }` block. Kept apart from `discord_token` so bot/application
+/// operations never need a personal user token.
+#[derive(Debug, Default, Clone)]
+pub struct BotConfig {
+    pub token: Option<String>,
+    pub channel_id: Option<String>,
+    pub post: Option<bool>,
+    pub about: Option<bool>,
+}
+
+/// Read a `discord_bot` block into `bot`, keeping fields the block doesn't set (so a profile can change
+/// just the channel).
+fn parse_bot_block(node: &kdl::KdlNode, bot: &mut BotConfig) {
+    let Some(children) = node.children() else { return };
+    for n in children.nodes() {
+        match n.name().value() {
+            "token" => bot.token = get_string(n),
+            "channel_id" => bot.channel_id = get_string(n),
+            "post" => bot.post = get_bool(n),
+            "about" => bot.about = get_bool(n),
+            _ => {}
+        }
+    }
+}
+
+fn parse_cleanup_block(node: &kdl::KdlNode) -> Vec<RuleSpec> {
+    let Some(children) = node.children() else { return Vec::new() };
+    children
+        .nodes()
+        .iter()
+        .filter(|n| n.name().value() == "rule")
+        .filter_map(|n| {
+            Some(RuleSpec {
+                name: get_string(n)?,
+                enabled: n.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true),
+                pattern: n.get("pattern").and_then(|v| v.as_string()).map(str::to_string),
+                replace: n.get("replace").and_then(|v| v.as_string()).map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// `aliases { artist "beatles" "The Beatles"; track "/^(.*) - Single$/" "$1" }`
+fn parse_aliases_block(node: &kdl::KdlNode) -> Vec<AliasSpec> {
+    let Some(children) = node.children() else { return Vec::new() };
+    children
+        .nodes()
+        .iter()
+        .filter_map(|n| {
+            let field = match n.name().value() {
+                "artist" => AliasField::Artist,
+                "track" => AliasField::Track,
+                _ => return None,
+            };
+            let mut args = n.entries().iter().filter(|e| e.name().is_none()).filter_map(|e| e.value().as_string());
+            Some(AliasSpec { field, pattern: args.next()?.to_string(), replacement: args.next()?.to_string() })
+        })
+        .collect()
+}
+
+/// `filters { exclude artist "Rain Sounds"; include track "*remix*" }`
+fn parse_filters_block(node: &kdl::KdlNode) -> Vec<FilterSpec> {
+    let Some(children) = node.children() else { return Vec::new() };
+    children
+        .nodes()
+        .iter()
+        .filter_map(|n| {
+            let action = match n.name().value() {
+                "include" => FilterAction::Include,
+                "exclude" => FilterAction::Exclude,
+                _ => return None,
+            };
+            let mut args = n.entries().iter().filter(|e| e.name().is_none()).filter_map(|e| e.value().as_string());
+            let field = match args.next()? {
+                "artist" => FilterField::Artist,
+                "track" => FilterField::Track,
+                _ => return None,
+            };
+            Some(FilterSpec { action, field, pattern: args.next()?.to_string() })
+        })
+        .collect()
+}
+
+/// `romanized_names { "宇多田ヒカル" "Hikaru Utada" }`: each child node is named after the original spelling.
+fn parse_romanized_names(node: &kdl::KdlNode) -> Vec<(String, String)> {
+    let Some(children) = node.children() else { return Vec::new() };
+    children
+        .nodes()
+        .iter()
+        .filter_map(|n| Some((n.name().value().to_string(), get_string(n)?)))
+        .collect()
+}
+
+fn parse_preset_block(node: &kdl::KdlNode) -> Option<(String, Preset)> {
+    let name = get_string(node)?;
+    let mut preset = Preset::default();
+    if let Some(children) = node.children() {
+        for n in children.nodes() {
+            match n.name().value() {
+                "format" => preset.format = get_string(n),
+                "join" => preset.join = get_string(n),
+                "prefix" => preset.prefix = get_string(n),
+                "suffix" => preset.suffix = get_string(n),
+                "list_template" => preset.list_template = get_string(n),
+                _ => {}
+            }
+        }
+    }
+    Some((name, preset))
+}
+
+/// Presets shipped with the binary; a `preset` block with the same name in the config replaces one.
+pub fn builtin_presets() -> BTreeMap<String, Preset> {
+    let doc: kdl::KdlDocument = BUILTIN_PRESETS_KDL.parse().expect("built-in presets are valid KDL");
+    doc.nodes().iter().filter_map(parse_preset_block).collect()
+}
+
+/// Every preset `--preset` can select: the built-in ones, extended or replaced by the config's `preset` blocks.
+pub fn available_presets(cfg: Option<&Config>) -> BTreeMap<String, Preset> {
+    let mut presets = builtin_presets();
+    if let Some(c) = cfg {
+        presets.extend(c.presets.clone());
+    }
+    presets
 }
 
 fn get_string(node: &kdl::KdlNode) -> Option<String> {
-    node.entries().get(0)?.value().as_string().map(|s| s.to_string())
+    node.entries().first()?.value().as_string().map(|s| s.to_string())
+}
+fn get_strings(node: &kdl::KdlNode) -> Option<Vec<String>> {
+    let values: Vec<String> = node
+        .entries()
+        .iter()
+        .filter(|e| e.name().is_none())
+        .filter_map(|e| e.value().as_string().map(|s| s.to_string()))
+        .collect();
+    if values.is_empty() { None } else { Some(values) }
 }
 fn get_bool(node: &kdl::KdlNode) -> Option<bool> {
-    node.entries().get(0)?.value().as_bool()
+    node.entries().first()?.value().as_bool()
 }
 fn get_u32(node: &kdl::KdlNode) -> Option<u32> {
-    node.entries().get(0)?.value().as_integer().and_then(|v| u32::try_from(v).ok())
+    node.entries().first()?.value().as_integer().and_then(|v| u32::try_from(v).ok())
+}
+fn get_u64(node: &kdl::KdlNode) -> Option<u64> {
+    node.entries().first()?.value().as_integer().and_then(|v| u64::try_from(v).ok())
 }
 fn get_usize(node: &kdl::KdlNode) -> Option<usize> {
-    node.entries().get(0)?.value().as_integer().and_then(|v| usize::try_from(v).ok())
+    node.entries().first()?.value().as_integer().and_then(|v| usize::try_from(v).ok())
 }
 
 // Return the ordered list of paths we will search for the config file
@@ -55,52 +268,176 @@ pub fn config_search_locations() -> Vec<PathBuf> {
 }
 
 pub fn find_config_path() -> Option<PathBuf> {
-    for p in config_search_locations() {
-        if p.exists() { return Some(p); }
-    }
-    None
+    config_search_locations().into_iter().find(|p| p.exists())
 }
 
-pub fn load_config() -> Option<Config> {
+/// Load the config file. When `profile` names a `profile` block, its keys are applied on top of the
+/// top-level ones; if no such block exists `Config::profile` stays `None` and the caller reports it.
+pub fn load_config(profile: Option<&str>) -> Option<Config> {
     let path = find_config_path()?;
     let content = fs::read_to_string(&path).ok()?;
-    let doc: kdl::KdlDocument = content.parse().ok()?;
+    parse_config(&content, profile)
+}
+
+/// Parse the contents of a config file, applying `profile` as [`load_config`] does. `None` when it isn't valid KDL.
+pub fn parse_config(source: &str, profile: Option<&str>) -> Option<Config> {
+    let doc: kdl::KdlDocument = source.parse().ok()?;
 
-    // Support either a root node `topsongs { ... }` or flat top-level entries
-    let node_span;
-    let nodes: Vec<kdl::KdlNode> = if let Some(n) = doc.get("topsongs") {
-        node_span = n.children().cloned();
-        if let Some(children) = node_span { children.nodes().into_iter().cloned().collect() } else { vec![] }
+    // Support either a root node `topsongs { ... }` or flat top-level entries. Profiles may sit inside the
+    // block or next to it.
+    let mut nodes: Vec<&kdl::KdlNode> = Vec::new();
+    if let Some(n) = doc.get("topsongs") {
+        nodes.extend(n.children().map(|c| c.nodes()).unwrap_or_default());
+        nodes.extend(doc.nodes().iter().filter(|n| n.name().value() == "profile"));
     } else {
-        doc.nodes().into_iter().cloned().collect()
-    };
+        nodes.extend(doc.nodes());
+    }
 
     let mut cfg = Config::default();
+    let mut selected = None;
     for n in nodes {
-        match n.name().value() {
-            "username" => cfg.username = get_string(&n),
-            "api_key" => cfg.api_key = get_string(&n),
-            "period" => cfg.period = get_string(&n),
-            "limit" => cfg.limit = get_u32(&n),
-            "select" => cfg.select = get_usize(&n),
-            "format" => cfg.format = get_string(&n),
-            "join" => cfg.join = get_string(&n),
-            "prefix" => cfg.prefix = get_string(&n),
-            "suffix" => cfg.suffix = get_string(&n),
-            "strip_feat" => cfg.strip_feat = get_bool(&n),
-            "strip_feat_regex" => cfg.strip_feat_regex = get_string(&n),
-            "copy" => cfg.copy = get_bool(&n),
-            "discord_token" => cfg.discord_token = get_string(&n),
-            "discord_bio_regex" => cfg.discord_bio_regex = get_string(&n),
-            "update_discord" => cfg.update_discord = get_bool(&n),
-            "discord_dry_run" => cfg.discord_dry_run = get_bool(&n),
-            "debug" => cfg.debug = get_bool(&n),
-            _ => {}
+        if n.name().value() != "profile" {
+            apply_node(&mut cfg, n);
+            continue;
+        }
+        let Some(name) = get_string(n) else { continue };
+        let children: Vec<&kdl::KdlNode> =
+            n.children().map(|c| c.nodes().iter().filter(|c| c.name().value() != "profile").collect()).unwrap_or_default();
+        let username = children.iter().rev().find(|c| c.name().value() == "username").and_then(|c| get_string(c));
+        let keys = children.iter().flat_map(|c| node_keys(c)).collect();
+        if profile == Some(name.as_str()) {
+            selected = Some((name.clone(), children));
+        }
+        // A later block with the same name replaces an earlier one
+        cfg.profiles.retain(|p| p.name != name);
+        cfg.profiles.push(ProfileSummary { name, username, keys });
+    }
+
+    if let Some((name, children)) = selected {
+        for n in children {
+            clear_alternatives(&mut cfg, n.name().value());
+            apply_node(&mut cfg, n);
+            cfg.profile_keys.extend(node_keys(n));
         }
+        cfg.profile = Some(name);
     }
     Some(cfg)
 }
 
+/// The keys a node sets, as shown by `config show --origin` (one per field for the bot block).
+fn node_keys(n: &kdl::KdlNode) -> Vec<String> {
+    match (n.name().value(), n.children()) {
+        ("discord_bot", Some(children)) => children.nodes().iter().map(|c| format!("discord_bot.{}", c.name().value())).collect(),
+        (name, _) => vec![name.to_string()],
+    }
+}
+
+/// Settings that can be given in several forms use the first form that is set, so a profile setting one
+/// form has to drop the top-level value of the others (e.g. its `api_key_file` must beat a top-level `api_key`).
+fn clear_alternatives(cfg: &mut Config, key: &str) {
+    match key {
+        "api_key" | "api_key_file" | "api_key_command" => {
+            cfg.api_key = None;
+            cfg.api_key_file = None;
+            cfg.api_key_command = None;
+        }
+        "discord_token" | "discord_token_file" | "discord_token_command" => {
+            cfg.discord_token = None;
+            cfg.discord_token_file = None;
+            cfg.discord_token_command = None;
+        }
+        "list_template" | "list_template_file" => {
+            cfg.list_template = None;
+            cfg.list_template_file = None;
+        }
+        _ => {}
+    }
+}
+
+fn apply_node(cfg: &mut Config, n: &kdl::KdlNode) {
+    match n.name().value() {
+        "username" => cfg.username = get_string(n),
+        "api_key" => cfg.api_key = get_string(n),
+        "period" => cfg.period = get_string(n),
+        "limit" => cfg.limit = get_u32(n),
+        "select" => cfg.select = get_usize(n),
+        "format" => cfg.format = get_string(n),
+        "join" => cfg.join = get_string(n),
+        "prefix" => cfg.prefix = get_string(n),
+        "suffix" => cfg.suffix = get_string(n),
+        "list_template" => cfg.list_template = get_string(n),
+        "list_template_file" => cfg.list_template_file = get_string(n),
+        "discord_markdown" => cfg.discord_markdown = get_bool(n),
+        "strip_feat" => cfg.strip_feat = get_bool(n),
+        "strip_feat_regex" => cfg.strip_feat_regex = get_string(n),
+        "cleanup" => cfg.cleanup = Some(parse_cleanup_block(n)),
+        "aliases" => cfg.aliases = parse_aliases_block(n),
+        "filters" => cfg.filters = parse_filters_block(n),
+        "move_feat" => cfg.move_feat = get_bool(n),
+        "merge" => cfg.merge = get_bool(n),
+        "transliterate" => cfg.transliterate = get_bool(n),
+        "romanized_names" => cfg.romanized_names = parse_romanized_names(n),
+        "featured_separator" => cfg.featured_separator = get_string(n),
+        "copy" => cfg.copy = get_bool(n),
+        "output_format" => cfg.output_format = get_string(n),
+        "table" => cfg.table = get_bool(n),
+        "discord_token" => cfg.discord_token = get_string(n),
+        "discord_token_file" => cfg.discord_token_file = get_string(n),
+        "discord_token_command" => cfg.discord_token_command = get_string(n),
+        "api_key_file" => cfg.api_key_file = get_string(n),
+        "api_key_command" => cfg.api_key_command = get_string(n),
+        "discord_bio_regex" => cfg.discord_bio_regex = get_string(n),
+        "discord_bio_limit" => cfg.discord_bio_limit = get_usize(n),
+        "bio_fit" => cfg.bio_fit = get_string(n),
+        "fallback_format" => cfg.fallback_format = get_string(n),
+        "update_discord" => cfg.update_discord = get_bool(n),
+        "discord_dry_run" => cfg.discord_dry_run = get_bool(n),
+        "discord_confirm" => cfg.discord_confirm = get_bool(n),
+        "discord_on_conflict" => cfg.discord_on_conflict = get_string(n),
+        "discord_targets" => cfg.discord_targets = get_string(n),
+        "status_format" => cfg.status_format = get_string(n),
+        "status_emoji" => cfg.status_emoji = get_string(n),
+        "status_expires" => cfg.status_expires = get_string(n),
+        "pronouns_format" => cfg.pronouns_format = get_string(n),
+        "discord_guild_ids" => cfg.discord_guild_ids = get_strings(n),
+        "webhook" => cfg.webhook = get_bool(n),
+        "webhook_url" => cfg.webhook_url = get_string(n),
+        "webhook_embed" => cfg.webhook_embed = get_bool(n),
+        "webhook_username" => cfg.webhook_username = get_string(n),
+        "presence_client_id" => cfg.presence_client_id = get_string(n),
+        "presence_details" => cfg.presence_details = get_string(n),
+        "presence_state" => cfg.presence_state = get_string(n),
+        "presence_interval" => cfg.presence_interval = get_u64(n),
+        "discord_bot" => parse_bot_block(n, &mut cfg.bot),
+        "preset" => {
+            if let Some((name, preset)) = parse_preset_block(n) {
+                cfg.presets.insert(name, preset);
+            }
+        }
+        "default_preset" => cfg.default_preset = get_string(n),
+        "debug" => cfg.debug = get_bool(n),
+        _ => {}
+    }
+}
+
+
+// Built-in presets, in the same syntax as `preset` blocks in the config file
+pub const BUILTIN_PRESETS_KDL: &str = r###"
+preset "discord" {
+    format "  - {artist} - {track}"
+    join "\n"
+    prefix "**On Loop**:\n"
+}
+preset "markdown" {
+    format "{rank}. **{track}** — {artist} ({playcount} plays)"
+    join "\n"
+    prefix "## Top tracks\n\n"
+}
+preset "plain" {
+    format "{rank}. {artist} - {track}"
+    join "\n"
+}
+"###;
 
 // Example KDL configuration embedded here for convenience
 pub const EXAMPLE_KDL: &str = r#"// topsongs.config.kdl
@@ -111,9 +448,13 @@ pub const EXAMPLE_KDL: &str = r#"// topsongs.config.kdl
 // .http templates live under the same config directory, in the 'http' subfolder.
 // You can wrap settings inside a `topsongs { ... }` block or keep them flat at the root.
 // Strings should be quoted; numbers are bare; booleans use #true/#false (KDL 2.0).
+// Precedence: built-in defaults < this file < the selected profile < TOPSONGS_<KEY> environment variables (e.g. TOPSONGS_LIMIT=20,
+// TOPSONGS_DISCORD_BOT_CHANNEL_ID) < command-line flags. `topsongs config show --origin` prints every
+// effective value and where it came from. `topsongs config check` reports unknown keys, values of the wrong
+// type and invalid choices (each run also warns about them); such values are ignored.
 // Note: To create barebones .http templates, run: topsongs --generate-http
 //   - With no value: creates all missing default templates in <config_dir>/http
-//   - With a value: creates a specific one if missing (one of: lastfm_top_tracks | discord_get_me | discord_patch_bio)
+//   - With a value: creates a specific one if missing (e.g. lastfm_top_tracks | discord_patch_bio | discord_patch_status)
 
 // Escape sequences: `\n` becomes a newline in prefix/suffix/join and inside format.
 // Selection: omit `select` to choose tracks interactively; set `select N` to auto-pick the top N.
@@ -121,7 +462,9 @@ pub const EXAMPLE_KDL: &str = r#"// topsongs.config.kdl
 topsongs {
     // Required for Last.fm
     username "your_lastfm_username" // your Last.fm account name
-    api_key "your_lastfm_api_key"   // or set env LASTFM_API_KEY
+    api_key "your_lastfm_api_key"   // or set env LASTFM_API_KEY, or `topsongs secret set api_key`
+    //api_key_file "~/.config/topsongs/lastfm_api_key" // read the key from a file instead
+    //api_key_command "pass show lastfm/api_key"      // or from a command's output
 
     // Optional defaults
     period "overall"   // overall | 7day | 1month | 3month | 6month | 12month
@@ -129,31 +472,180 @@ topsongs {
     //select 3         // optional: auto-include top N; omit to choose interactively
 
     // Rendering
-    format "  - {artist} - {track}" // tokens: {artist}, {track}, {playcount}
+    // Templates: tokens {rank}, {artist}, {track}, {playcount}; filters upper, lower, trim, truncate:N,
+    // pad:N (left-aligned), lpad:N (right-aligned), e.g. {artist|truncate:20}; conditionals
+    // {?playcount>100}🔥{/} (also >=, <, <=, ==, != or a bare {?name}); write {{ and }} for literal braces.
+    // prefix/suffix/join are templates too and can use {count}.
+    // Discord Markdown: {track|bold}, {artist|italic}, {artist|spoiler}, {track|strike}, {track|code},
+    // {url|link:track} (a [track](url) link) or {url|link} (<url>, no embed preview).
+    // {track|romanize} transliterates Cyrillic, Greek, kana and Hangul to Latin letters.
+    format "  - {artist} - {track}"
     join "\n"                     // string between rows
     //prefix "**On Loop**:\n"    // text before the list
     //suffix ""                 // text after the list
 
+    // Whole-list template (replaces prefix/join/suffix). Loop with {#for tracks}...{/}; inside the loop use
+    // the entry tokens, {entry} (the entry rendered with `format`) and {loop.index}, {loop.first}, {loop.last}.
+    // Anywhere: {count}, {total_plays}, {period}, {username}, {date}.
+    //list_template "**Top {count}** ({total_plays} plays)\n{#for tracks}{?loop.first}👑{/}{entry}{?!loop.last}\n{/}{/for}"
+    //list_template_file "bio.tpl" // relative paths are looked up in the current directory, then next to this config
+    discord_markdown #false // escape * _ ~ ` | \ in names so they don't break the bio's formatting
+
+    // Presets bundle format/join/prefix/suffix/list_template under a name; pick one with --preset <name>.
+    // Built-in: "discord", "markdown", "plain". A block with the same name replaces the built-in one.
+    // A preset replaces the top-level format/join/prefix/suffix/list_template entirely (unset fields use
+    // the defaults); explicit --format/--join/--prefix/--suffix still win over the preset's values.
+    //default_preset "discord"
+    //preset "wiki" {
+    //    format "| {rank} | {track} | {artist} | {playcount} |"
+    //    join "\n"
+    //    prefix "| # | Track | Artist | Plays |\n|---|---|---|---|\n"
+    //}
+
     // Title cleanup
     strip_feat #true     // remove "feat." and similar from track titles
     strip_feat_regex "(?i)\\s*(?:[\\(\\[]\\s*(?:feat\\.?|ft\\.?|with)\\b.*?[\\)\\]]|-\\s*(?:feat\\.?|ft\\.?|with)\\b.*)$"
 
+    // Featured artists: "(feat. A, B & C)" in a title is always available as {featured} and, together
+    // with the main artist, as {artists}. move_feat moves them out of the title into {artist}.
+    //move_feat #true
+    //featured_separator " & " // or ", "
+
+    // Aliases: fix Last.fm's artist and track names before anything else (cleanup, merging, rendering).
+    // A plain name matches the whole value, ignoring case; /.../ is a regex and the replacement can use $1.
+    // The first matching alias wins. See what was rewritten with --show-aliases-applied.
+    //aliases {
+    //    artist "beatles" "The Beatles"
+    //    track "SONG NAME" "Song Name"
+    //    track "/^(.*) - Single Version$/" "$1"
+    //}
+
+    // Filters: hide tracks you don't want in public lists. A plain name matches exactly (ignoring case),
+    // * and ? make a glob, /.../ is a regex. Artist filters also look at featured artists, track filters at
+    // the title before and after cleanup. Any exclude hides a track; with include rules, only matching tracks
+    // are shown. More tracks are fetched so the list still reaches limit/select entries.
+    // --include-artist/--include-track/--exclude-artist/--exclude-track add to these. There are no tag
+    // filters: Last.fm's top tracks come without tags, and looking them up costs one request per track.
+    //filters {
+    //    exclude artist "Rain Sounds"
+    //    exclude track "*white noise*"
+    //    exclude artist "/(?i)podcast/"
+    //}
+
+    // Duplicates: entries with the same artist and title after cleanup (ignoring case, punctuation and
+    // remaster/edit/edition tags, even when cleanup keeps those) are merged into one with their playcounts
+    // summed, then the list is re-ranked. Disable with --no-merge.
+    //merge #false
+
+    // Transliteration: convert Cyrillic, Greek, Japanese kana and Hangul in titles and artists to Latin
+    // letters (kanji are kept). Artists listed in romanized_names use their official spelling instead; an
+    // entry matches a whole artist name (ignoring case), never part of a title. Check the result with
+    // `topsongs text romanize "<text>"`.
+    //transliterate #true
+    //romanized_names {
+    //    "宇多田ヒカル" "Hikaru Utada"
+    //    "방탄소년단" "BTS"
+    //}
+
+    // Title cleanup pipeline: when this block is present it replaces strip_feat. Rules run in order.
+    // Built-in rules:
+    //   feat                 "(feat. X)", "[ft. X]", "- with X"
+    //   remaster             "- 2015 Remaster", "- Remastered 2011", "(2009 Digital Remaster)"
+    //   live                 "(Live at Wembley)", "[Live]", "- Live from Tokyo"
+    //   edit_mix             "- Radio Edit", "(Single Version)", "(Extended Mix)", "- Mono" (credited remixes are kept)
+    //   edition              "(Deluxe Edition)", "(Taylor's Version)", "[From The Vault]", "(Bonus Track)"
+    //   explicit             "[Explicit]", "(Clean)", "(Explicit Version)"
+    //   collapse_whitespace  squeeze repeated spaces
+    // Custom rules take a regex `pattern` and an optional `replace`; any rule can be switched off with enabled=#false.
+    // Try it with: topsongs text test "Song - Remastered 2011 (Live at Wembley) [Explicit]"
+    //cleanup {
+    //    rule "feat"
+    //    rule "remaster"
+    //    rule "live" enabled=#false
+    //    rule "edit_mix"
+    //    rule "edition"
+    //    rule "explicit"
+    //    rule "bonus" pattern="(?i)\\s*\\(bonus track\\)$"
+    //    rule "collapse_whitespace"
+    //}
+
     // Convenience
     copy #false          // copy final output to clipboard (Windows only)
+    //output_format "text" // text | json | csv | markdown | html (for --query and the final list)
+    //table #true         // show fetched tracks as an aligned table (CJK/emoji-aware column widths)
     debug #false         // verbose HTTP logging; shows request line/headers and error bodies
 
     // Discord (manual updates preferred; use --discord-dry-run/--update-discord if needed)
-    // Provide your user token only if you intend to use Discord operations
-    discord_token ""
-    // Regex to find the section in your current bio to replace
-    discord_bio_regex "/\\*\\*[\\w ]+\\*\\*:?[\r]?(\n[ \\w-]+)+\n/"
+    // Profile fields (bio, status, pronouns) can only be changed with your personal user token. Automating a
+    // user account is against Discord's ToS; prefer the webhook or the `discord_bot` block below where possible.
+    // Provide your user token only if you intend to use those operations, and keep it out of this file:
+    //   topsongs secret set discord_token   (encrypted with a passphrase; TOPSONGS_PASSPHRASE skips the prompt)
+    //discord_token_file "~/.config/topsongs/discord_token" // or read it from a file
+    //discord_token_command "pass show discord/token"       // or from a command's output
+    //discord_token ""                                      // plain text; least safe
+    // Regex to find the section in your current bio to replace. List lines may hold the `\` escapes and
+    // `*~`|` that discord_markdown produces.
+    discord_bio_regex "/\\*\\*[\\w ]+\\*\\*:?[\r]?(\n[ \\w\\\\*~`|-]+)+\n/"
+    // Length budget: the final bio is measured before sending; over-long bios are auto-fit or refused
+    //discord_bio_limit 190      // Discord's About Me limit (counted in code points)
+    //bio_fit "fallback,truncate,drop" // strategies tried in order: drop | truncate | fallback
+    //fallback_format "  - {track}" // shorter entry format used by the `fallback` strategy
     //update_discord #true       // perform actual PATCH to update the bio (requires token and templates)
     //discord_dry_run #true      // preview the replacement only; no PATCH
+    //discord_confirm #false     // skip the diff confirmation before a real update (same as --yes)
+    //discord_on_conflict "abort" // bio edited in Discord while we were busy: abort | reapply
+
+    // Other profile fields, updated with the same --discord-dry-run/--update-discord semantics
+    //discord_targets "bio,status" // any of: bio | status | pronouns | guild-bio
+    // Only the bio is backed up for `discord undo`; status, pronouns and server bios are overwritten without a record
+    //status_format "{artist} - {track}" // custom status, rendered with the first selected track
+    //status_emoji "🎵"
+    //status_expires "1d"        // clear the status after 30m / 4h / 1d; omit to keep it
+    //pronouns_format "{artist} enjoyer" // max 40 characters
+    //discord_guild_ids "123456789012345678" // servers whose server-specific bio the guild-bio target replaces
+
+    // Channel webhook: post the chosen tracks to e.g. #music (needs discord_webhook.http; see --generate-http)
+    //webhook #true              // post on every run (same as --webhook)
+    //webhook_url "https://discord.com/api/webhooks/<id>/<token>" // or env DISCORD_WEBHOOK_URL, or `topsongs secret set webhook_url`
+    //webhook_embed #true        // rich embed with rank, title, artist, playcount and Last.fm link
+    //webhook_username "TopSongs"
+
+    // Bot/application mode: uses a bot token with the `Bot` authorization scheme, never your user token
+    //discord_bot {
+    //    token "your_bot_token"           // or env DISCORD_BOT_TOKEN, or `topsongs secret set discord_bot_token`
+    //    channel_id "123456789012345678"  // channel the bot posts the chosen tracks to
+    //    post #true                        // post to channel_id on every run (same as --bot-post)
+    //    about #false                      // replace the bot's own About Me with the list (same as --bot-about)
+    //}
+
+    // Rich Presence (--presence): shown through the locally running Discord client, no token needed
+    //presence_client_id "123456789012345678" // your Discord application id
+    //presence_details "{track}"
+    //presence_state "by {artist}"
+    //presence_interval 60       // seconds between updates (minimum 15)
+
+    // Profiles: named sets of overrides for other accounts or people sharing this file, applied with
+    // --profile NAME or TOPSONGS_PROFILE=NAME. Keys a profile doesn't set come from above; blocks such as
+    // aliases, filters and cleanup are replaced as a whole, discord_bot fields one by one.
+    // `topsongs config profiles` lists them.
+    //profile "second-account" {
+    //    username "your_other_lastfm_username"
+    //    discord_token_file "~/.config/topsongs/other_token"
+    //    period "7day"
+    //}
 }
 "#;
 
 
 // Preferred config directory (platform-aware). Falls back to current working directory if env not set.
+/// Expand a leading `~/` to $HOME; other paths are returned unchanged.
+pub fn expand_home(path: &str) -> std::path::PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => std::path::PathBuf::from(home).join(rest),
+        _ => std::path::PathBuf::from(path),
+    }
+}
+
 pub fn config_dir() -> std::path::PathBuf {
     if cfg!(target_os = "windows") {
         if let Some(appdata) = std::env::var_os("APPDATA") {
@@ -170,3 +662,114 @@ pub fn config_dir() -> std::path::PathBuf {
 pub fn http_dir() -> std::path::PathBuf {
     config_dir().join("http")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_parse() {
+        let presets = builtin_presets();
+        assert_eq!(presets.keys().map(String::as_str).collect::<Vec<_>>(), ["discord", "markdown", "plain"]);
+        let discord = &presets["discord"];
+        assert_eq!(discord.format.as_deref(), Some("  - {artist} - {track}"));
+        assert_eq!(discord.join.as_deref(), Some("\n"));
+        assert_eq!(discord.prefix.as_deref(), Some("**On Loop**:\n"));
+        assert!(presets.values().all(|p| p.format.is_some()));
+    }
+
+    #[test]
+    fn config_preset_replaces_a_builtin_one() {
+        let mine = Preset { format: Some("{track}".to_string()), ..Preset::default() };
+        let cfg = Config {
+            presets: BTreeMap::from([("discord".to_string(), mine), ("short".to_string(), Preset::default())]),
+            ..Config::default()
+        };
+        let presets = available_presets(Some(&cfg));
+        assert_eq!(presets["discord"].format.as_deref(), Some("{track}"));
+        // The replacement is whole: fields it leaves out are not taken from the built-in preset
+        assert_eq!(presets["discord"].prefix, None);
+        assert!(presets.contains_key("short") && presets.contains_key("markdown"));
+        assert_eq!(available_presets(None).len(), 3);
+    }
+
+    #[test]
+    fn profile_keys_override_the_top_level() {
+        let source = r#"
+            topsongs {
+                username "alice"
+                limit 10
+                period "7day"
+                discord_bot { channel_id "1"; post #true }
+                profile "work" {
+                    username "alice_work"
+                    limit 5
+                    discord_bot { channel_id "2" }
+                }
+            }
+        "#;
+        let cfg = parse_config(source, Some("work")).unwrap();
+        assert_eq!(cfg.profile.as_deref(), Some("work"));
+        assert_eq!((cfg.username.as_deref(), cfg.limit, cfg.period.as_deref()), (Some("alice_work"), Some(5), Some("7day")));
+        // The bot block is merged field by field
+        assert_eq!((cfg.bot.channel_id.as_deref(), cfg.bot.post), (Some("2"), Some(true)));
+        assert_eq!(cfg.profile_keys, ["username", "limit", "discord_bot.channel_id"]);
+
+        let cfg = parse_config(source, None).unwrap();
+        assert_eq!((cfg.profile, cfg.username.as_deref(), cfg.limit), (None, Some("alice"), Some(10)));
+        assert_eq!(cfg.profiles.len(), 1);
+        assert_eq!(cfg.profiles[0].username.as_deref(), Some("alice_work"));
+        // An unknown profile applies nothing; the caller reports it
+        let cfg = parse_config(source, Some("home")).unwrap();
+        assert_eq!((cfg.profile, cfg.username.as_deref()), (None, Some("alice")));
+    }
+
+    #[test]
+    fn profile_form_of_a_setting_beats_other_top_level_forms() {
+        let source = r#"
+            api_key "top-level"
+            discord_token_command "pass discord"
+            profile "work" {
+                api_key_file "~/.work-key"
+            }
+        "#;
+        let cfg = parse_config(source, Some("work")).unwrap();
+        assert_eq!(cfg.api_key, None);
+        assert_eq!(cfg.api_key_file.as_deref(), Some("~/.work-key"));
+        // Settings the profile doesn't touch keep every form
+        assert_eq!(cfg.discord_token_command.as_deref(), Some("pass discord"));
+    }
+
+    #[test]
+    fn duplicate_profile_names_use_the_last_block() {
+        let source = r#"
+            username "alice"
+            profile "work" { username "first"; limit 3 }
+            profile "work" { username "second" }
+        "#;
+        let cfg = parse_config(source, Some("work")).unwrap();
+        assert_eq!(cfg.username.as_deref(), Some("second"));
+        // Nothing from the replaced block is applied
+        assert_eq!(cfg.limit, None);
+        assert_eq!(cfg.profiles.len(), 1);
+        assert_eq!(cfg.profiles[0].username.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn profiles_may_sit_outside_the_topsongs_block() {
+        let source = r#"
+            topsongs {
+                username "alice"
+                profile "inside" { username "in" }
+            }
+            profile "outside" { username "out" }
+            username "ignored next to a topsongs block"
+        "#;
+        let cfg = parse_config(source, Some("outside")).unwrap();
+        assert_eq!(cfg.username.as_deref(), Some("out"));
+        let names: Vec<&str> = cfg.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["inside", "outside"]);
+        assert_eq!(parse_config(source, None).unwrap().username.as_deref(), Some("alice"));
+        assert!(parse_config("topsongs {", None).is_none());
+    }
+}
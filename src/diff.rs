@@ -0,0 +1,184 @@
+use regex::Regex;
+use similar::{ChangeTag, TextDiff};
+use std::ops::Range;
+
+use crate::cli::ConflictPolicy;
+use crate::discord::bio_length;
+
+fn paint(code: &str, s: &str) -> String {
+    // Respect https://no-color.org so piped or logged output stays readable
+    if std::env::var_os("NO_COLOR").is_some() {
+        s.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", code, s)
+    }
+}
+
+/// Line-level diff of two bios, with the changed parts inside modified lines emphasised.
+/// Unchanged lines are kept for context so the result reads like the bio itself.
+pub fn render_bio_diff(old: &str, new: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = String::new();
+    for op in diff.ops() {
+        for change in diff.iter_inline_changes(op) {
+            let (sign, color, emphasis) = match change.tag() {
+                ChangeTag::Delete => ("-", "31", "1;4;31"),
+                ChangeTag::Insert => ("+", "32", "1;4;32"),
+                ChangeTag::Equal => (" ", "2", "2"),
+            };
+            let mut line = paint(color, &format!("{} ", sign));
+            for (emphasized, value) in change.iter_strings_lossy() {
+                let value = value.trim_end_matches(['\n', '\r']);
+                if value.is_empty() {
+                    continue;
+                }
+                line.push_str(&paint(if emphasized { emphasis } else { color }, value));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// One-line summary of the length change, e.g. "Characters: 120 -> 143 (+23) of 190".
+pub fn length_summary(old: &str, new: &str, limit: usize) -> String {
+    let (before, after) = (bio_length(old), bio_length(new));
+    let delta = after as i64 - before as i64;
+    format!("Characters: {} -> {} ({:+}) of {}", before, after, delta, limit)
+}
+
+/// 1-based line number of the byte offset `byte`; offsets inside a multi-byte character count as that character.
+fn line_of(text: &str, byte: usize) -> usize {
+    text[..text.floor_char_boundary(byte)].matches('\n').count() + 1
+}
+
+/// Describe where the bio regex matched and flag matches that look unintended:
+/// more than one candidate region, or a region that does not start with the configured prefix.
+pub fn match_notes(bio: &str, matches: &[Range<usize>], prefix: &str) -> Vec<String> {
+    let mut notes = Vec::new();
+    let Some(first) = matches.first() else { return notes };
+    let total_lines = bio.lines().count().max(1);
+    let end = first.end.saturating_sub(1).max(first.start);
+    notes.push(format!(
+        "Regex matched lines {}-{} of {}",
+        line_of(bio, first.start),
+        line_of(bio, end),
+        total_lines
+    ));
+    if matches.len() > 1 {
+        notes.push(format!(
+            "Note: the regex matches {} regions; only the first is replaced. Tighten --discord-bio-regex if that is not the one you expected.",
+            matches.len()
+        ));
+    }
+    let expected_head = prefix.lines().map(str::trim).find(|l| !l.is_empty());
+    if let Some(head) = expected_head
+        && !bio[first.clone()].trim_start().starts_with(head)
+    {
+        notes.push(format!(
+            "Note: the matched region does not start with your prefix \"{}\"; the regex may have matched a different section than expected.",
+            head
+        ));
+    }
+    notes
+}
+
+/// What to do with the bio re-fetched right before the PATCH.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConflictOutcome {
+    /// Send `bio`, replacing (and backing up) `base`
+    Send { base: String, bio: String },
+    /// The bio changed meanwhile and the replacement was applied to the latest version instead
+    Reapplied { base: String, bio: String },
+    /// The bio changed meanwhile and already contains the list
+    UpToDate,
+    /// Nothing is sent, for the given reason
+    Abort(String),
+}
+
+/// Decide what to send when the bio is `latest` right before the update, given that `new_bio` was built from `expected`.
+/// `rebuild` re-applies the replacement to another bio; the result must still fit in `limit`.
+pub fn resolve_conflict(
+    expected: &str,
+    latest: &str,
+    new_bio: &str,
+    policy: ConflictPolicy,
+    re: &Regex,
+    limit: usize,
+    rebuild: impl FnOnce(&str) -> String,
+) -> ConflictOutcome {
+    if latest == expected {
+        return ConflictOutcome::Send { base: expected.to_string(), bio: new_bio.to_string() };
+    }
+    match policy {
+        ConflictPolicy::Abort => ConflictOutcome::Abort(
+            "Your Discord bio changed since it was fetched. No update sent; re-run to apply against the latest bio (or use --on-conflict reapply).".to_string(),
+        ),
+        ConflictPolicy::Reapply if !re.is_match(latest) => ConflictOutcome::Abort(
+            "Your Discord bio changed since it was fetched and the regex no longer matches it. No update sent.".to_string(),
+        ),
+        ConflictPolicy::Reapply => {
+            let rebuilt = rebuild(latest);
+            if bio_length(&rebuilt) > limit {
+                ConflictOutcome::Abort(format!(
+                    "Your Discord bio changed since it was fetched; re-applying the list makes it {} characters, over the limit of {}. No update sent.",
+                    bio_length(&rebuilt),
+                    limit
+                ))
+            } else if rebuilt == latest {
+                ConflictOutcome::UpToDate
+            } else {
+                ConflictOutcome::Reapplied { base: latest.to_string(), bio: rebuilt }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_notes_handle_non_ascii_match_end() {
+        let bio = "**Top**:\n- é";
+        let whole = 0..bio.len();
+        assert_eq!(match_notes(bio, std::slice::from_ref(&whole), "**Top**"), ["Regex matched lines 1-2 of 2"]);
+        let bio = "intro\n**Top**:\n- 宇多田\n- 🎧\nbye";
+        let end = bio.find("bye").unwrap();
+        let notes = match_notes(bio, &[6..end, end..bio.len()], "**Top**");
+        assert_eq!(notes[0], "Regex matched lines 2-4 of 5");
+        assert!(notes[1].contains("matches 2 regions"));
+    }
+
+    fn rebuild(bio: &str) -> String {
+        Regex::new(r"(?s)\*\*Top\*\*:.*").unwrap().replace(bio, "**Top**:\n- New").to_string()
+    }
+
+    #[test]
+    fn unchanged_bio_sends_the_new_one() {
+        let re = Regex::new(r"(?s)\*\*Top\*\*:.*").unwrap();
+        let outcome = resolve_conflict("hi\n**Top**:\n- Old", "hi\n**Top**:\n- Old", "hi\n**Top**:\n- New", ConflictPolicy::Abort, &re, 190, rebuild);
+        assert_eq!(outcome, ConflictOutcome::Send { base: "hi\n**Top**:\n- Old".to_string(), bio: "hi\n**Top**:\n- New".to_string() });
+    }
+
+    #[test]
+    fn changed_bio_aborts_by_default() {
+        let re = Regex::new(r"(?s)\*\*Top\*\*:.*").unwrap();
+        let outcome = resolve_conflict("**Top**:\n- Old", "edited\n**Top**:\n- Old", "**Top**:\n- New", ConflictPolicy::Abort, &re, 190, rebuild);
+        assert!(matches!(outcome, ConflictOutcome::Abort(m) if m.contains("--on-conflict reapply")));
+    }
+
+    #[test]
+    fn reapply_uses_the_latest_bio_while_the_regex_still_matches() {
+        let re = Regex::new(r"(?s)\*\*Top\*\*:.*").unwrap();
+        let outcome = resolve_conflict("**Top**:\n- Old", "edited\n**Top**:\n- Old", "**Top**:\n- New", ConflictPolicy::Reapply, &re, 190, rebuild);
+        assert_eq!(outcome, ConflictOutcome::Reapplied { base: "edited\n**Top**:\n- Old".to_string(), bio: "edited\n**Top**:\n- New".to_string() });
+        let outcome = resolve_conflict("**Top**:\n- Old", "list removed", "**Top**:\n- New", ConflictPolicy::Reapply, &re, 190, rebuild);
+        assert!(matches!(outcome, ConflictOutcome::Abort(m) if m.contains("no longer matches")));
+        let outcome = resolve_conflict("**Top**:\n- Old", "x\n**Top**:\n- New", "**Top**:\n- New", ConflictPolicy::Reapply, &re, 190, rebuild);
+        assert_eq!(outcome, ConflictOutcome::UpToDate);
+        let outcome = resolve_conflict("**Top**:\n- Old", "long bio\n**Top**:", "**Top**:\n- New", ConflictPolicy::Reapply, &re, 10, rebuild);
+        assert!(matches!(outcome, ConflictOutcome::Abort(m) if m.contains("over the limit of 10")));
+    }
+}
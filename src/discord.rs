@@ -1,73 +1,164 @@
 ﻿use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
 use serde::Deserialize;
-use std::fs;
 
-use crate::http_template::{apply_substitution, build_request_from_spec, build_vars_map, parse_http_spec};
-use crate::net::send_with_debug;
+use crate::http_template::send_template;
+
+/// Discord's "About Me" limit for user profiles.
+pub const DEFAULT_BIO_LIMIT: usize = 190;
+/// Discord's limit for the pronouns field.
+pub const PRONOUNS_LIMIT: usize = 40;
+/// Discord's limit for custom status text.
+pub const STATUS_TEXT_LIMIT: usize = 128;
+
+/// Length of a bio as Discord validates it: Unicode code points, with CRLF collapsed to a single newline.
+pub fn bio_length(bio: &str) -> usize {
+    bio.replace("\r\n", "\n").chars().count()
+}
 
 #[derive(Debug, Deserialize)]
 struct DiscordUser {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    username: String,
     bio: Option<String>,
 }
 
-pub async fn get_current_bio(token: &str, debug: bool) -> Result<String> {
-    // Prefer config http dir; fall back to legacy ./http
-    let preferred = crate::config::http_dir().join("discord_get_me.http");
-    let legacy = std::path::Path::new("http\\discord_get_me.http").to_path_buf();
-    let chosen = if preferred.exists() { preferred } else { legacy };
-    let client = reqwest::Client::new();
-    let resp = if chosen.exists() {
-        let content = fs::read_to_string(&chosen)
-            .with_context(|| format!("Failed to read .http file at {}", chosen.to_string_lossy()))?;
-        let spec = parse_http_spec(&content)?;
-        // Only substitute token or env vars; headers like UA/locale/etc must be hardcoded in the .http file
-        let vars = build_vars_map(&[("DISCORD_TOKEN", token.to_string())]);
-        let spec = apply_substitution(spec, &vars);
-        let (rb, body_preview) = build_request_from_spec(&client, &spec)?;
-        send_with_debug(rb, debug, body_preview).await?
-    } else {
-        // Required .http file missing
-        return Err(anyhow!(
-            format!(
-                "Required discord_get_me.http not found in {} or legacy ./http. Run with --generate-http to create templates.",
-                crate::config::http_dir().display()
-            )
-        ));
-    };
+/// The account a token belongs to, with its current bio.
+#[derive(Debug, Clone)]
+pub struct DiscordProfile {
+    pub id: String,
+    pub username: String,
+    pub bio: String,
+}
+
+pub async fn get_profile(token: &str, debug: bool) -> Result<DiscordProfile> {
+    // Only substitute token or env vars; headers like UA/locale/etc must be hardcoded in the .http file
+    let resp = send_template("discord_get_me.http", &[("DISCORD_TOKEN", token.to_string())], debug).await?;
     let text = resp.text().await?;
     let user: DiscordUser = serde_json::from_str(&text)
         .context("Failed to parse Discord user profile JSON")?;
-    Ok(user.bio.unwrap_or_default())
+    if user.id.is_empty() {
+        return Err(anyhow!("Discord user profile JSON has no account id"));
+    }
+    Ok(DiscordProfile { id: user.id, username: user.username, bio: user.bio.unwrap_or_default() })
+}
+
+pub async fn get_current_bio(token: &str, debug: bool) -> Result<String> {
+    Ok(get_profile(token, debug).await?.bio)
+}
+
+/// Escape `s` for injection between the quotes of a JSON string in a .http template.
+fn json_string_contents(s: &str) -> String {
+    serde_json::to_string(s)
+        .map(|j| j[1..j.len() - 1].to_string())
+        .unwrap_or_else(|_| s.to_string())
+}
+
+/// A JSON literal for an optional string: `"..."` when present, `null` otherwise.
+fn json_optional(s: Option<&str>) -> String {
+    s.map(|v| format!("\"{}\"", json_string_contents(v))).unwrap_or_else(|| "null".to_string())
 }
 
 pub async fn update_bio(token: &str, new_bio: &str, debug: bool) -> Result<()> {
-    // Prefer config http dir; fall back to legacy ./http
-    let preferred = crate::config::http_dir().join("discord_patch_bio.http");
-    let legacy = std::path::Path::new("http\\discord_patch_bio.http").to_path_buf();
-    let chosen = if preferred.exists() { preferred } else { legacy };
-    let client = reqwest::Client::new();
-    if chosen.exists() {
-        let content = fs::read_to_string(&chosen)
-            .with_context(|| format!("Failed to read .http file at {}", chosen.to_string_lossy()))?;
-        let spec = parse_http_spec(&content)?;
-        // Only substitute token/new bio. All header values must be hardcoded in the .http file.
-        // IMPORTANT: The .http template wraps {{NEW_BIO}} in quotes, so we must inject a JSON-escaped string content
-        // without surrounding quotes. Use serde_json to produce a JSON string and strip the outer quotes.
-        let json_escaped = serde_json::to_string(new_bio)
-            .map(|s| s[1..s.len()-1].to_string())
-            .unwrap_or_else(|_| new_bio.to_string());
-        let vars = build_vars_map(&[("DISCORD_TOKEN", token.to_string()), ("NEW_BIO", json_escaped)]);
-        let spec = apply_substitution(spec, &vars);
-        let (rb, body_preview) = build_request_from_spec(&client, &spec)?;
-        let _resp = send_with_debug(rb, debug, body_preview).await?;
-        Ok(())
-    } else {
-        // Required .http file missing
-        return Err(anyhow!(
-            format!(
-                "Required discord_patch_bio.http not found in {} or legacy ./http. Run with --generate-http to create templates.",
-                crate::config::http_dir().display()
-            )
-        ));
+    // IMPORTANT: The .http template wraps {{NEW_BIO}} in quotes, so we must inject a JSON-escaped string content
+    // without surrounding quotes.
+    let vars = [("DISCORD_TOKEN", token.to_string()), ("NEW_BIO", json_string_contents(new_bio))];
+    send_template("discord_patch_bio.http", &vars, debug).await?;
+    Ok(())
+}
+
+/// Set the custom status. `emoji` is a unicode emoji (custom emoji are not supported by the settings endpoint
+/// without an id); `expires_at` of `None` keeps the status until it is changed.
+pub async fn update_custom_status(
+    token: &str,
+    text: &str,
+    emoji: Option<&str>,
+    expires_at: Option<DateTime<Utc>>,
+    debug: bool,
+) -> Result<()> {
+    let expires = expires_at.map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true));
+    let vars = [
+        ("DISCORD_TOKEN", token.to_string()),
+        ("STATUS_TEXT", json_string_contents(text)),
+        ("STATUS_EMOJI_JSON", json_optional(emoji)),
+        ("STATUS_EXPIRES_AT_JSON", json_optional(expires.as_deref())),
+    ];
+    send_template("discord_patch_status.http", &vars, debug).await?;
+    Ok(())
+}
+
+pub async fn update_pronouns(token: &str, pronouns: &str, debug: bool) -> Result<()> {
+    let vars = [("DISCORD_TOKEN", token.to_string()), ("PRONOUNS", json_string_contents(pronouns))];
+    send_template("discord_patch_pronouns.http", &vars, debug).await?;
+    Ok(())
+}
+
+/// Discord ids (snowflakes) are plain decimal numbers; anything else must not reach a URL path.
+pub fn is_snowflake(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Replace the server-specific bio shown in the given guild.
+pub async fn update_guild_bio(token: &str, guild_id: &str, new_bio: &str, debug: bool) -> Result<()> {
+    if !is_snowflake(guild_id) {
+        return Err(anyhow!("Invalid guild id '{}': expected the numeric server id", guild_id));
+    }
+    let vars = [
+        ("DISCORD_TOKEN", token.to_string()),
+        ("GUILD_ID", guild_id.to_string()),
+        ("NEW_BIO", json_string_contents(new_bio)),
+    ];
+    send_template("discord_patch_guild_bio.http", &vars, debug).await?;
+    Ok(())
+}
+
+/// Parse a status lifetime such as "30m", "4h" or "1d", or an RFC 3339 time, into an absolute expiry time.
+pub fn parse_status_expiry(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: i64 = num.parse().map_err(|_| anyhow!("Invalid status expiry '{}': expected e.g. 30m, 4h, 1d or 2024-05-01T18:00:00Z", s))?;
+    let delta = match unit {
+        "m" => TimeDelta::minutes(n),
+        "h" => TimeDelta::hours(n),
+        "d" => TimeDelta::days(n),
+        _ => return Err(anyhow!("Invalid status expiry '{}': unit must be m, h or d", s)),
+    };
+    Ok(Utc::now() + delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_expiry_accepts_durations_and_timestamps() {
+        let before = Utc::now();
+        let in_30m = parse_status_expiry("30m").unwrap();
+        assert!(in_30m >= before + TimeDelta::minutes(30) && in_30m <= Utc::now() + TimeDelta::minutes(30));
+        let in_2h = parse_status_expiry(" 2h ").unwrap();
+        assert!(in_2h >= before + TimeDelta::hours(2) && in_2h <= Utc::now() + TimeDelta::hours(2));
+        let at = parse_status_expiry("2030-05-01T20:00:00+02:00").unwrap();
+        assert_eq!(at.to_rfc3339_opts(SecondsFormat::Secs, true), "2030-05-01T18:00:00Z");
+    }
+
+    #[test]
+    fn status_expiry_rejects_garbage() {
+        assert!(parse_status_expiry("soon").unwrap_err().to_string().contains("expected e.g. 30m"));
+        assert!(parse_status_expiry("5y").unwrap_err().to_string().contains("unit must be m, h or d"));
+        assert!(parse_status_expiry("").is_err());
+    }
+
+    #[test]
+    fn snowflakes_are_digits_only() {
+        assert!(is_snowflake("81384788765712384"));
+        assert!(!is_snowflake(""));
+        assert!(!is_snowflake("../users/@me"));
+        assert!(!is_snowflake("123/roles"));
     }
 }
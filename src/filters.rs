@@ -0,0 +1,174 @@
+use regex::Regex;
+
+use crate::text::normalize_pattern;
+
+/// Whether a filter keeps or hides the tracks it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Include,
+    Exclude,
+}
+
+/// Which name a filter looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterField {
+    Artist,
+    Track,
+}
+
+/// One `include`/`exclude` line of the `filters` block, or one `--include-*`/`--exclude-*` flag.
+#[derive(Debug, Clone)]
+pub struct FilterSpec {
+    pub action: FilterAction,
+    pub field: FilterField,
+    pub pattern: String,
+}
+
+/// A name pattern: `/.../` is a regex, `*` and `?` make a glob, anything else is an exact name.
+/// Exact names and globs ignore case.
+#[derive(Debug)]
+enum NamePattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl NamePattern {
+    fn parse(pattern: &str) -> Result<NamePattern, regex::Error> {
+        if pattern.len() >= 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+            Regex::new(&normalize_pattern(pattern)).map(NamePattern::Regex)
+        } else if pattern.contains(['*', '?']) {
+            let glob: String = pattern
+                .trim()
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            Regex::new(&format!("(?i)^{}$", glob)).map(NamePattern::Regex)
+        } else {
+            Ok(NamePattern::Exact(pattern.trim().to_lowercase()))
+        }
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            NamePattern::Exact(name) => value.trim().to_lowercase() == *name,
+            NamePattern::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Filter {
+    action: FilterAction,
+    field: FilterField,
+    pattern: NamePattern,
+}
+
+/// Include/exclude rules for the fetched tracks. A track is hidden if any exclude rule matches it, or if
+/// there are include rules and none of them matches. Only names are filtered: `user.getTopTracks` returns
+/// no tags, and fetching them would take a `track.getTopTags` request per track.
+#[derive(Debug, Default)]
+pub struct TrackFilters {
+    filters: Vec<Filter>,
+}
+
+impl TrackFilters {
+    /// Compile `specs`. Every invalid regex is reported, not just the first.
+    pub fn from_specs(specs: &[FilterSpec]) -> Result<TrackFilters, Vec<String>> {
+        let mut filters = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs {
+            match NamePattern::parse(&spec.pattern) {
+                Ok(pattern) => filters.push(Filter { action: spec.action, field: spec.field, pattern }),
+                Err(e) => errors.push(format!("filter {:?}: invalid pattern: {}", spec.pattern, e)),
+            }
+        }
+        if errors.is_empty() { Ok(TrackFilters { filters }) } else { Err(errors) }
+    }
+
+    /// Decide whether a track stays. `artists` are the credited artists (main and featured) and `titles`
+    /// the spellings of its title; a rule matches if it matches any of them.
+    pub fn keep(&self, artists: &[&str], titles: &[&str]) -> bool {
+        let matches = |f: &Filter| {
+            let values = match f.field {
+                FilterField::Artist => artists,
+                FilterField::Track => titles,
+            };
+            values.iter().any(|v| f.pattern.matches(v))
+        };
+        let mut includes = self.filters.iter().filter(|f| f.action == FilterAction::Include).peekable();
+        let included = includes.peek().is_none() || includes.any(matches);
+        included && !self.filters.iter().filter(|f| f.action == FilterAction::Exclude).any(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(specs: &[(FilterAction, FilterField, &str)]) -> TrackFilters {
+        let specs: Vec<FilterSpec> =
+            specs.iter().map(|(action, field, pattern)| FilterSpec { action: *action, field: *field, pattern: pattern.to_string() }).collect();
+        TrackFilters::from_specs(&specs).unwrap()
+    }
+
+    #[test]
+    fn exact_names_ignore_case_and_surrounding_space() {
+        let f = filters(&[(FilterAction::Exclude, FilterField::Artist, " Rain Sounds ")]);
+        assert!(!f.keep(&["rain sounds"], &["Storm"]));
+        assert!(f.keep(&["Rain Sounds Club"], &["Storm"]));
+        // Featured artists count
+        assert!(!f.keep(&["DJ", "Rain Sounds"], &["Storm"]));
+    }
+
+    #[test]
+    fn globs_match_the_whole_name() {
+        let f = filters(&[(FilterAction::Exclude, FilterField::Track, "*white noise*"), (FilterAction::Exclude, FilterField::Track, "Track ?")]);
+        assert!(!f.keep(&["A"], &["10 Hours of WHITE NOISE for sleep"]));
+        assert!(!f.keep(&["A"], &["Track 1"]));
+        assert!(f.keep(&["A"], &["Track 10"]));
+        // Regex characters in a glob are literal
+        let f = filters(&[(FilterAction::Include, FilterField::Track, "(*)")]);
+        assert!(f.keep(&["A"], &["(Intro)"]));
+        assert!(!f.keep(&["A"], &["Intro"]));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_and_keep_their_case_rules() {
+        let f = filters(&[(FilterAction::Exclude, FilterField::Artist, "/(?i)podcast/"), (FilterAction::Exclude, FilterField::Track, "/^Ep\\. \\d+/")]);
+        assert!(!f.keep(&["The Daily Podcast"], &["News"]));
+        assert!(!f.keep(&["Host"], &["Ep. 12 - Guests"]));
+        assert!(f.keep(&["Host"], &["ep. 12 - Guests"]));
+        // Any spelling of the title counts (before and after cleanup)
+        assert!(!f.keep(&["Host"], &["Song", "Ep. 3"]));
+    }
+
+    #[test]
+    fn excludes_win_over_includes() {
+        let f = filters(&[
+            (FilterAction::Include, FilterField::Artist, "Queen"),
+            (FilterAction::Include, FilterField::Artist, "Daft Punk"),
+            (FilterAction::Exclude, FilterField::Track, "*live*"),
+        ]);
+        assert!(f.keep(&["Queen"], &["Bohemian Rhapsody"]));
+        assert!(f.keep(&["Daft Punk"], &["One More Time"]));
+        assert!(!f.keep(&["Björk"], &["Jóga"]));
+        assert!(!f.keep(&["Queen"], &["Bohemian Rhapsody (Live)"]));
+        // No rules keep everything
+        assert!(TrackFilters::default().keep(&["Anyone"], &["Anything"]));
+    }
+
+    #[test]
+    fn every_invalid_regex_is_reported() {
+        let specs: Vec<FilterSpec> = ["/(/", "ok", "/[/"]
+            .iter()
+            .map(|p| FilterSpec { action: FilterAction::Exclude, field: FilterField::Track, pattern: p.to_string() })
+            .collect();
+        let errors = TrackFilters::from_specs(&specs).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("filter \"/(/\": invalid pattern"));
+    }
+}
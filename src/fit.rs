@@ -0,0 +1,151 @@
+use crate::cli::FitStrategy;
+use crate::lastfm::Track;
+use crate::render::{render_list, render_list_template, ListContext};
+
+/// Shortest title the `truncate` strategy will produce (including the ellipsis).
+const MIN_TITLE_CHARS: usize = 4;
+
+/// Everything needed to re-render the list while searching for a version that fits.
+#[derive(Default)]
+pub struct FitInput<'a> {
+    pub tracks: &'a [Track],
+    pub format: &'a str,
+    pub fallback_format: Option<&'a str>,
+    pub join: &'a str,
+    pub prefix: &'a str,
+    pub suffix: &'a str,
+    /// Whole-list template that replaces prefix/join/suffix when set
+    pub list_template: Option<(&'a str, &'a ListContext<'a>)>,
+    /// Escape token values for Discord Markdown
+    pub markdown: bool,
+}
+
+pub struct FitOutcome {
+    /// The rendered list (prefix + entries + suffix) that was settled on
+    pub output: String,
+    /// Final length as reported by the `measure` callback
+    pub length: usize,
+    /// Human-readable notes for each strategy that changed the output
+    pub applied: Vec<String>,
+    /// Strategies that were needed but could not do anything (e.g. `fallback` without a fallback format)
+    pub warnings: Vec<String>,
+    pub fits: bool,
+}
+
+struct State<'a> {
+    format: &'a str,
+    tracks: Vec<Track>,
+}
+
+impl State<'_> {
+    fn render(&self, input: &FitInput) -> String {
+        match input.list_template {
+            Some((tpl, ctx)) => render_list_template(tpl, &self.tracks, self.format, ctx, input.markdown),
+            None => render_list(&self.tracks, self.format, input.join, input.prefix, input.suffix, input.markdown),
+        }
+    }
+}
+
+/// Shorten `title` to at most `max_chars` code points, ending in an ellipsis when cut.
+pub fn truncate_with_ellipsis(title: &str, max_chars: usize) -> String {
+    if title.chars().count() <= max_chars {
+        return title.to_string();
+    }
+    // Cut on grapheme boundaries so accents and emoji sequences are never split in half.
+    let kept = crate::text::take_chars(title, max_chars.saturating_sub(1));
+    format!("{}…", kept.trim_end())
+}
+
+/// Render the list and, if `measure(output)` exceeds `limit`, apply `strategies` in order until it fits.
+/// `measure` receives the rendered list and returns the length of the final text it ends up in
+/// (e.g. the whole bio after the regex replacement), so surrounding text counts against the budget.
+pub fn fit_to_limit(
+    input: &FitInput,
+    limit: usize,
+    strategies: &[FitStrategy],
+    measure: impl Fn(&str) -> usize,
+) -> FitOutcome {
+    let mut state = State { format: input.format, tracks: input.tracks.to_vec() };
+    let mut applied = Vec::new();
+    let mut warnings = Vec::new();
+    let mut output = state.render(input);
+    let mut length = measure(&output);
+
+    for strategy in strategies {
+        if length <= limit {
+            break;
+        }
+        match strategy {
+            FitStrategy::Fallback => match input.fallback_format {
+                Some(fallback) if fallback != state.format => {
+                    state.format = fallback;
+                    output = state.render(input);
+                    length = measure(&output);
+                    applied.push(format!("switched to fallback format \"{}\"", fallback));
+                }
+                _ => warnings.push("bio_fit fallback skipped: no fallback format different from the format is configured".to_string()),
+            },
+            FitStrategy::Truncate => {
+                let longest = state.tracks.iter().map(|t| t.name.chars().count()).max().unwrap_or(0);
+                let originals: Vec<String> = state.tracks.iter().map(|t| t.name.clone()).collect();
+                let mut cap = longest;
+                while length > limit && cap > MIN_TITLE_CHARS {
+                    cap -= 1;
+                    for (t, orig) in state.tracks.iter_mut().zip(&originals) {
+                        t.name = truncate_with_ellipsis(orig, cap);
+                    }
+                    output = state.render(input);
+                    length = measure(&output);
+                }
+                if cap < longest {
+                    applied.push(format!("truncated titles to {} characters", cap));
+                }
+            }
+            FitStrategy::Drop => {
+                let before = state.tracks.len();
+                while length > limit && state.tracks.len() > 1 {
+                    state.tracks.pop();
+                    output = state.render(input);
+                    length = measure(&output);
+                }
+                let dropped = before - state.tracks.len();
+                if dropped > 0 {
+                    applied.push(format!("dropped {} track(s) from the end", dropped));
+                }
+            }
+        }
+    }
+
+    FitOutcome { output, length, applied, warnings, fits: length <= limit }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str) -> Track {
+        Track { name: name.to_string(), ..Default::default() }
+    }
+
+    fn input<'a>(tracks: &'a [Track], fallback_format: Option<&'a str>) -> FitInput<'a> {
+        FitInput { tracks, format: "{track}", fallback_format, join: "\n", ..Default::default() }
+    }
+
+    #[test]
+    fn skipped_fallback_is_a_warning_not_an_applied_strategy() {
+        let tracks = [track("A long title"), track("Another long title")];
+        let fitted = fit_to_limit(&input(&tracks, None), 20, &[FitStrategy::Fallback, FitStrategy::Drop], |s| s.chars().count());
+        assert_eq!(fitted.applied, ["dropped 1 track(s) from the end"]);
+        assert_eq!(fitted.warnings.len(), 1);
+        assert!(fitted.fits);
+    }
+
+    #[test]
+    fn strategies_stop_once_the_list_fits() {
+        let tracks = [track("A long title"), track("Another long title")];
+        let fitted = fit_to_limit(&input(&tracks, Some("x")), 20, &[FitStrategy::Fallback, FitStrategy::Drop], |s| s.chars().count());
+        assert_eq!(fitted.output, "x\nx");
+        assert_eq!(fitted.applied, ["switched to fallback format \"x\""]);
+        assert!(fitted.warnings.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Oldest backups of an account are discarded once it has more than this many entries.
+const MAX_ENTRIES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BioBackup {
+    pub id: u32,
+    pub timestamp: DateTime<Utc>,
+    /// Discord user id the bio belongs to; empty for backups recorded before accounts were tracked.
+    #[serde(default)]
+    pub account: String,
+    #[serde(default)]
+    pub username: String,
+    pub bio: String,
+}
+
+pub fn history_path() -> PathBuf {
+    crate::config::config_dir().join("bio_history.json")
+}
+
+pub fn load_history() -> Result<Vec<BioBackup>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read bio history at {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse bio history at {}", path.display()))
+}
+
+/// The history keeps every past bio, so like the secret store it is written to a sibling created with mode 0600
+/// and renamed into place.
+fn save_history(entries: &[BioBackup]) -> Result<()> {
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create config directory {}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(entries)?;
+    let tmp = path.with_extension("json.tmp");
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let write = || -> std::io::Result<()> {
+        let mut file = options.open(&tmp)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    };
+    write().with_context(|| format!("Failed to write bio history at {}", path.display()))
+}
+
+/// Store `bio` as the newest backup of `account` and return its id.
+/// Consecutive identical bios of the same account are not duplicated; the existing entry's id is returned instead.
+pub fn record_backup(account: &str, username: &str, bio: &str) -> Result<u32> {
+    let mut entries = load_history()?;
+    let id = push_backup(&mut entries, account, username, bio, Utc::now());
+    save_history(&entries)?;
+    Ok(id)
+}
+
+fn push_backup(entries: &mut Vec<BioBackup>, account: &str, username: &str, bio: &str, timestamp: DateTime<Utc>) -> u32 {
+    if let Some(last) = entries.iter().rev().find(|e| e.account == account)
+        && last.bio == bio
+    {
+        return last.id;
+    }
+    let id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+    entries.push(BioBackup {
+        id,
+        timestamp,
+        account: account.to_string(),
+        username: username.to_string(),
+        bio: bio.to_string(),
+    });
+    let owned = entries.iter().filter(|e| e.account == account).count();
+    if owned > MAX_ENTRIES {
+        let mut excess = owned - MAX_ENTRIES;
+        entries.retain(|e| {
+            if excess > 0 && e.account == account {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+    id
+}
+
+pub fn find_backup(id: u32) -> Result<Option<BioBackup>> {
+    Ok(load_history()?.into_iter().find(|e| e.id == id))
+}
+
+/// The newest backup recorded for `account`. Backups of other accounts, and legacy ones without an account, are never picked.
+pub fn latest_backup(account: &str) -> Result<Option<BioBackup>> {
+    Ok(load_history()?.into_iter().rev().find(|e| e.account == account))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backups(list: &[(&str, &str)]) -> Vec<BioBackup> {
+        let mut entries = vec![];
+        for (account, bio) in list {
+            push_backup(&mut entries, account, "", bio, Utc::now());
+        }
+        entries
+    }
+
+    #[test]
+    fn dedupes_against_the_same_account_only() {
+        let mut entries = backups(&[("a", "one"), ("b", "one")]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(push_backup(&mut entries, "a", "", "one", Utc::now()), 1);
+        assert_eq!(push_backup(&mut entries, "b", "", "two", Utc::now()), 3);
+    }
+
+    #[test]
+    fn trims_only_the_recording_account() {
+        let mut entries = backups(&[("b", "keep")]);
+        for i in 0..MAX_ENTRIES + 2 {
+            push_backup(&mut entries, "a", "", &i.to_string(), Utc::now());
+        }
+        assert_eq!(entries.iter().filter(|e| e.account == "a").count(), MAX_ENTRIES);
+        assert_eq!(entries[0].bio, "keep");
+        assert_eq!(entries[1].bio, "2");
+    }
+
+    #[test]
+    fn legacy_entries_default_to_no_account() {
+        let entries: Vec<BioBackup> =
+            serde_json::from_str(r#"[{"id":1,"timestamp":"2024-01-01T00:00:00Z","bio":"old"}]"#).unwrap();
+        assert_eq!(entries[0].account, "");
+    }
+}
@@ -1,7 +1,93 @@
-﻿use anyhow::{anyhow, Result};
+﻿use anyhow::{anyhow, Context, Result};
 use regex::Regex;
 use reqwest::RequestBuilder;
 use std::collections::HashMap;
+use std::fs;
+
+use crate::net::send_with_debug;
+
+/// Barebones templates written by `--generate-http` (no personal info), keyed by file stem.
+pub const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "lastfm_top_tracks",
+        "GET https://ws.audioscrobbler.com/2.0/?method=user.gettoptracks&user={{USERNAME}}&period={{PERIOD}}&api_key={{API_KEY}}&format=json&limit={{LIMIT}}\n",
+    ),
+    (
+        "discord_get_me",
+        "GET https://discord.com/api/v10/users/@me\nAuthorization: {{DISCORD_TOKEN}}\n",
+    ),
+    (
+        "discord_patch_bio",
+        concat!(
+            "PATCH https://discord.com/api/v9/users/@me/profile\n",
+            "Content-Type: application/json\n",
+            "Authorization: {{DISCORD_TOKEN}}\n",
+            "\n",
+            "{\n  \"bio\": \"{{NEW_BIO}}\"\n}\n",
+        ),
+    ),
+    (
+        "discord_patch_status",
+        concat!(
+            "PATCH https://discord.com/api/v9/users/@me/settings\n",
+            "Content-Type: application/json\n",
+            "Authorization: {{DISCORD_TOKEN}}\n",
+            "# STATUS_EMOJI_JSON and STATUS_EXPIRES_AT_JSON are JSON literals (a quoted string or null), so leave them unquoted\n",
+            "\n",
+            "{\n  \"custom_status\": {\n    \"text\": \"{{STATUS_TEXT}}\",\n    \"emoji_name\": {{STATUS_EMOJI_JSON}},\n    \"expires_at\": {{STATUS_EXPIRES_AT_JSON}}\n  }\n}\n",
+        ),
+    ),
+    (
+        "discord_patch_pronouns",
+        concat!(
+            "PATCH https://discord.com/api/v9/users/@me/profile\n",
+            "Content-Type: application/json\n",
+            "Authorization: {{DISCORD_TOKEN}}\n",
+            "\n",
+            "{\n  \"pronouns\": \"{{PRONOUNS}}\"\n}\n",
+        ),
+    ),
+    (
+        "discord_patch_guild_bio",
+        concat!(
+            "PATCH https://discord.com/api/v9/guilds/{{GUILD_ID}}/profile/@me\n",
+            "Content-Type: application/json\n",
+            "Authorization: {{DISCORD_TOKEN}}\n",
+            "\n",
+            "{\n  \"bio\": \"{{NEW_BIO}}\"\n}\n",
+        ),
+    ),
+    (
+        "discord_webhook",
+        concat!(
+            "POST {{WEBHOOK_URL}}\n",
+            "Content-Type: application/json\n",
+            "# WEBHOOK_PAYLOAD is the complete JSON message (content or embeds), built by topsongs\n",
+            "\n",
+            "{{WEBHOOK_PAYLOAD}}\n",
+        ),
+    ),
+    (
+        "discord_bot_post_message",
+        concat!(
+            "POST https://discord.com/api/v10/channels/{{CHANNEL_ID}}/messages\n",
+            "Content-Type: application/json\n",
+            "Authorization: Bot {{DISCORD_BOT_TOKEN}}\n",
+            "\n",
+            "{{MESSAGE_PAYLOAD}}\n",
+        ),
+    ),
+    (
+        "discord_bot_patch_about",
+        concat!(
+            "PATCH https://discord.com/api/v10/applications/@me\n",
+            "Content-Type: application/json\n",
+            "Authorization: Bot {{DISCORD_BOT_TOKEN}}\n",
+            "\n",
+            "{{ABOUT_PAYLOAD}}\n",
+        ),
+    ),
+];
 
 pub struct HttpSpec {
     pub method: String,
@@ -17,7 +103,7 @@ pub fn parse_http_spec(content: &str) -> Result<HttpSpec> {
     let mut lines = normalized.lines();
     // Skip initial empty/comment lines
     let mut first_line = None;
-    while let Some(line) = lines.next() {
+    for line in lines.by_ref() {
         let l = line.trim();
         if l.is_empty() || l.starts_with('#') {
             continue;
@@ -107,3 +193,32 @@ pub fn build_request_from_spec(client: &reqwest::Client, spec: &HttpSpec) -> Res
     }
     Ok((rb, body_preview))
 }
+
+/// Locate a .http template (config http dir first, then legacy ./http), substitute `vars` and send it.
+pub async fn send_template(file_name: &str, vars: &[(&str, String)], debug: bool) -> Result<reqwest::Response> {
+    let preferred = crate::config::http_dir().join(file_name);
+    let legacy = std::path::Path::new("http").join(file_name);
+    let chosen = if preferred.exists() { preferred } else { legacy };
+    if !chosen.exists() {
+        // Required .http file missing
+        return Err(anyhow!(
+            "Required {} not found in {} or legacy ./http. Run with --generate-http to create templates.",
+            file_name,
+            crate::config::http_dir().display()
+        ));
+    }
+    let content = fs::read_to_string(&chosen)
+        .with_context(|| format!("Failed to read .http file at {}", chosen.to_string_lossy()))?;
+    send_template_content(&content, vars, debug).await
+}
+
+/// Substitute `vars` into the contents of a .http template and send it.
+pub async fn send_template_content(content: &str, vars: &[(&str, String)], debug: bool) -> Result<reqwest::Response> {
+    let client = reqwest::Client::new();
+    let spec = parse_http_spec(content)?;
+    // Only substitute the given values (and env vars). All header values must be hardcoded in the .http file.
+    let vars = build_vars_map(vars);
+    let spec = apply_substitution(spec, &vars);
+    let (rb, body_preview) = build_request_from_spec(&client, &spec)?;
+    send_with_debug(rb, debug, body_preview).await
+}
@@ -15,14 +15,32 @@ pub struct TopTracks {
     pub track: Vec<Track>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Track {
     pub name: String,
     pub playcount: String,
     pub artist: Artist,
+    #[serde(default)]
+    pub url: String,
+    /// Featured artists taken from the title, joined with the configured separator (empty when none)
+    #[serde(skip)]
+    pub featured: String,
+    /// Main artist plus featured artists, joined with the configured separator (empty until filled in)
+    #[serde(skip)]
+    pub artists: String,
+    /// How many other Last.fm entries were merged into this one as duplicates
+    #[serde(skip)]
+    pub merged: u32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl Track {
+    /// Main plus featured artists; just the main artist until featured credits have been extracted.
+    pub fn all_artists(&self) -> String {
+        if self.artists.is_empty() { self.artist.name.clone() } else { self.artists.clone() }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Artist {
     pub name: String,
 }
@@ -1,21 +1,33 @@
+mod bot;
 mod cli;
+mod aliases;
+mod filters;
 mod lastfm;
+mod merge;
 mod discord;
 mod http_template;
 mod net;
+mod output;
+mod presence;
 mod render;
+mod secrets;
+mod settings;
+mod template;
 mod text;
 mod clipboard;
 mod config;
+mod diff;
+mod fit;
+mod history;
 mod ui;
+mod validate;
+mod webhook;
 
 fn print_kdl_parse_errors(path: &std::path::Path, source: &str, err: &kdl::KdlError) {
     // Header
     eprintln!("Config file found at {} but failed to parse as KDL:", path.display());
 
     // Try to print structured diagnostics if available
-    let mut printed_any = false;
-
     // Try common access pattern: a `diagnostics` field on the error
     #[allow(unused_variables)]
     {
@@ -28,12 +40,12 @@ fn print_kdl_parse_errors(path: &std::path::Path, source: &str, err: &kdl::KdlEr
 
     // Attempt via field access (kdl 6.x exposes `diagnostics: Vec<KdlDiagnostic>`)
     #[allow(unused_variables)]
-    {
+    let printed_any = {
         // Try direct field access
         // If this compiles for kdl 6.5.0, it will use the embedded diagnostics
         let diags: &[kdl::KdlDiagnostic] = &err.diagnostics;
-        printed_any = print_diags_from_slice(source, diags);
-    }
+        print_diags_from_slice(source, diags)
+    };
 
     // Final fallback: print Display for the error
     if !printed_any {
@@ -47,22 +59,38 @@ fn print_diags_from_slice(source: &str, diags: &[kdl::KdlDiagnostic]) -> bool {
         any = true;
         let msg = d.message.as_deref().unwrap_or("KDL parse error");
         eprintln!("  - {}", msg);
-        // Use the main span to compute location
-        let start = d.span.offset();
-        let (line_no, col_no, line_text) = byte_range_to_line_col(source, start);
-        eprintln!("      at line {}, col {}", line_no, col_no);
-        let display_line = line_text.replace('\t', " ");
-        eprintln!("        {}", display_line);
-        let mut caret = String::new();
-        for _ in 1..col_no { caret.push(' '); }
-        caret.push('^');
-        eprintln!("        {}", caret);
+        print_source_snippet(source, d.span.offset(), 1);
         if let Some(label) = d.label.as_deref() { eprintln!("        note: {}", label); }
         if let Some(help) = d.help.as_deref() { eprintln!("    help: {}", help); }
     }
     any
 }
 
+/// Print the location of `offset` in `source` followed by the line itself with `len` bytes underlined.
+/// The caret is placed by display width, so wide characters before it don't shift it.
+fn print_source_snippet(source: &str, offset: usize, len: usize) {
+    let (line_no, col_no, line_text) = byte_range_to_line_col(source, offset);
+    eprintln!("      at line {}, col {}", line_no, col_no);
+    let display_line = line_text.replace('\t', " ");
+    eprintln!("        {}", display_line);
+    let start = (col_no - 1).min(display_line.len());
+    let start = (0..=start).rev().find(|i| display_line.is_char_boundary(*i)).unwrap_or(0);
+    let end = (start + len).min(display_line.len());
+    let end = (end..=display_line.len()).find(|i| display_line.is_char_boundary(*i)).unwrap_or(display_line.len());
+    let underline = display_width(&display_line[start..end]).max(1);
+    eprintln!("        {}{}", " ".repeat(display_width(&display_line[..start])), "^".repeat(underline));
+}
+
+/// Print validation problems found in the config file at `path`, in the same layout as KDL parse errors.
+fn print_config_issues(path: &std::path::Path, source: &str, issues: &[ConfigIssue]) {
+    eprintln!("Config file {} has {} problem{}:", path.display(), issues.len(), if issues.len() == 1 { "" } else { "s" });
+    for issue in issues {
+        eprintln!("  - {}", issue.message);
+        print_source_snippet(source, issue.offset, issue.len);
+        if let Some(help) = issue.help.as_deref() { eprintln!("    help: {}", help); }
+    }
+}
+
 fn byte_range_to_line_col(source: &str, byte_start: usize) -> (usize, usize, String) {
     let mut acc = 0usize;
     for (i, line) in source.split_inclusive(['\n', '\r']).enumerate() {
@@ -82,18 +110,48 @@ fn byte_range_to_line_col(source: &str, byte_start: usize) -> (usize, usize, Str
 }
 
 use std::env;
+use std::io::IsTerminal;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use regex::Regex;
+use regex::{NoExpand, Regex};
 
-use crate::cli::Cli;
-use crate::discord::{get_current_bio, update_bio};
+use crate::bot::{post_channel_message, update_bot_about, BOT_ABOUT_LIMIT};
+use crate::cli::{Cli, Command, DiscordCommand, DiscordTarget, FitStrategy, OutputFormat, SecretCommand, TextCommand, ConfigCommand};
+use crate::discord::{
+    bio_length, get_current_bio, get_profile, is_snowflake, parse_status_expiry, DiscordProfile, update_bio, update_custom_status, update_guild_bio, update_pronouns,
+    PRONOUNS_LIMIT, STATUS_TEXT_LIMIT,
+};
+use crate::diff::{length_summary, match_notes, render_bio_diff, resolve_conflict, ConflictOutcome};
+use crate::fit::{fit_to_limit, truncate_with_ellipsis, FitInput};
+use crate::http_template::DEFAULT_TEMPLATES;
 use crate::lastfm::{fetch_top_tracks, Track};
-use crate::render::{interpret_escapes, render_template};
-use crate::text::{normalize_pattern, strip_title};
+use crate::output::{aligned_table, format_tracks, ListMeta};
+use crate::presence::activity_text;
+use crate::render::{interpret_escapes, render_list, render_list_template, render_template, ListContext};
+use crate::aliases::{AliasField, Aliases};
+use crate::filters::{FilterAction, FilterField, FilterSpec, TrackFilters};
+use crate::settings::{credential_origins, selected_profile, ListTemplateSource, SettingOrigin, Settings};
+use crate::merge::{merge_duplicates, normalize_key, title_key};
+use crate::text::{display_width, extract_featured, normalize_pattern, pad_to_width, romanize, romanize_name, set_romanized_names, Align, Cleanup, RuleSpec};
 use crate::clipboard::copy_to_clipboard;
 use crate::config::load_config;
+use crate::validate::{validate_config, ConfigIssue};
+use crate::webhook::{build_payload, post_webhook, WebhookMessage};
+use crate::secrets::{SecretResolver, read_secret_command, read_secret_file};
+use crate::history::{find_backup, latest_backup, load_history, record_backup};
+
+/// Print a progress or result line: to stdout normally, to stderr when stdout carries machine-readable
+/// `--output-format` data, so piping the JSON/CSV into another tool keeps working.
+macro_rules! status {
+    ($machine_output:expr, $($arg:tt)*) => {
+        if $machine_output {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -124,47 +182,31 @@ async fn main() -> Result<()> {
     if let Some(which) = &cli.generate_http {
         use std::io::Write;
         let http_dir = crate::config::http_dir();
-        if !http_dir.exists() {
-            if let Err(e) = std::fs::create_dir_all(&http_dir) {
-                eprintln!("Failed to create http directory {}: {}", http_dir.display(), e);
-                std::process::exit(1);
-            }
+        if !http_dir.exists()
+            && let Err(e) = std::fs::create_dir_all(&http_dir)
+        {
+            eprintln!("Failed to create http directory {}: {}", http_dir.display(), e);
+            std::process::exit(1);
         }
 
-        // Barebones templates (no personal info)
-        let lastfm_content = "GET https://ws.audioscrobbler.com/2.0/?method=user.gettoptracks&user={{USERNAME}}&period={{PERIOD}}&api_key={{API_KEY}}&format=json&limit={{LIMIT}}\n";
-        let discord_get_content = "GET https://discord.com/api/v10/users/@me\nAuthorization: {{DISCORD_TOKEN}}\n";
-        let discord_patch_content = concat!(
-            "PATCH https://discord.com/api/v9/users/@me/profile\n",
-            "Content-Type: application/json\n",
-            "Authorization: {{DISCORD_TOKEN}}\n",
-            "\n",
-            "{\n  \"bio\": \"{{NEW_BIO}}\"\n}\n",
-        );
-
         let mut created_any = false;
         let want_all = which.eq("ALL") || which.eq_ignore_ascii_case("all");
-        let targets: Vec<(&str, &str)> = if want_all {
-            vec![
-                ("lastfm_top_tracks.http", lastfm_content),
-                ("discord_get_me.http", discord_get_content),
-                ("discord_patch_bio.http", discord_patch_content),
-            ]
+        let targets: Vec<(String, &str)> = if want_all {
+            DEFAULT_TEMPLATES.iter().map(|(name, content)| (format!("{}.http", name), *content)).collect()
         } else {
-            let (name, content) = match which.as_str() {
-                "lastfm_top_tracks" | "lastfm_top_tracks.http" => ("lastfm_top_tracks.http", lastfm_content),
-                "discord_get_me" | "discord_get_me.http" => ("discord_get_me.http", discord_get_content),
-                "discord_patch_bio" | "discord_patch_bio.http" => ("discord_patch_bio.http", discord_patch_content),
-                other => {
-                    eprintln!("Unknown template name: {}. Use one of: lastfm_top_tracks | discord_get_me | discord_patch_bio", other);
+            let wanted = which.strip_suffix(".http").unwrap_or(which);
+            match DEFAULT_TEMPLATES.iter().find(|(name, _)| *name == wanted) {
+                Some((name, content)) => vec![(format!("{}.http", name), *content)],
+                None => {
+                    let names: Vec<&str> = DEFAULT_TEMPLATES.iter().map(|(name, _)| *name).collect();
+                    eprintln!("Unknown template name: {}. Use one of: {}", which, names.join(" | "));
                     std::process::exit(1);
                 }
-            };
-            vec![(name, content)]
+            }
         };
 
         for (fname, content) in targets {
-            let path = http_dir.join(fname);
+            let path = http_dir.join(&fname);
             if path.exists() {
                 println!("Exists, not overwriting: {}", path.display());
                 continue;
@@ -193,7 +235,8 @@ async fn main() -> Result<()> {
 
     // Load optional config (KDL)
     let found_config_path = crate::config::find_config_path();
-    let cfg = load_config();
+    let profile = selected_profile(&cli);
+    let cfg = load_config(profile.as_deref());
 
     // If no config loaded, differentiate between not found vs found-but-invalid
     if cfg.is_none() {
@@ -225,10 +268,58 @@ async fn main() -> Result<()> {
         }
     }
 
-    // Determine early debug flag from CLI or config
-    let early_debug = if cli.debug { true } else { cfg.as_ref().and_then(|c| c.debug).unwrap_or(false) };
+    // Check the config for unknown keys and bad values. `config check` reports the result and stops; every
+    // other command just warns, since the rest of the file still loads.
+    let config_check = matches!(cli.command, Some(Command::Config { action: ConfigCommand::Check }));
+    let issues = match (&found_config_path, &cfg) {
+        (Some(p), Some(_)) => std::fs::read_to_string(p).map(|content| (validate_config(&content), content)).ok(),
+        _ => None,
+    };
+    if let (Some(p), Some((issues, content))) = (&found_config_path, &issues)
+        && !issues.is_empty()
+    {
+        print_config_issues(p, content, issues);
+    }
+    if config_check {
+        match (&found_config_path, &issues) {
+            (None, _) => {
+                eprintln!("No config file found. Searched locations:");
+                for p in crate::config::config_search_locations() {
+                    eprintln!("  - {}", p.display());
+                }
+            }
+            (Some(p), Some((issues, _))) if issues.is_empty() => {
+                println!("{}: no problems found", p.display());
+                return Ok(());
+            }
+            _ => {}
+        }
+        std::process::exit(1);
+    }
 
-    // If debug is enabled, print the config values as read from file (not the resolved effective values)
+    if matches!(cli.command, Some(Command::Config { action: ConfigCommand::Profiles })) {
+        print_profiles(cfg.as_ref(), found_config_path.as_deref(), profile.as_deref());
+        return Ok(());
+    }
+    // A profile that doesn't exist would silently fall back to the top-level account, so refuse to run.
+    if let Some(name) = &profile
+        && cfg.as_ref().and_then(|c| c.profile.as_ref()) != Some(name)
+    {
+        let known: Vec<&str> = cfg.iter().flat_map(|c| &c.profiles).map(|p| p.name.as_str()).collect();
+        if known.is_empty() {
+            eprintln!("Config error: unknown profile '{}'; the config file defines no profiles", name);
+        } else {
+            eprintln!("Config error: unknown profile '{}'. Available profiles: {}", name, known.join(", "));
+        }
+        std::process::exit(2);
+    }
+
+    // Layer defaults < config < profile < TOPSONGS_* env < CLI once; everything below reads the effective values from here.
+    let settings = Settings::resolve(&cli, cfg.as_ref());
+    let early_debug = settings.debug;
+
+    // If debug is enabled, print the config values as read from file (not the resolved effective values).
+    // Like all debug output this goes to stderr, so it never mixes with a machine-readable --output-format.
     if early_debug {
         match &cfg {
             Some(c) => {
@@ -241,168 +332,343 @@ async fn main() -> Result<()> {
                         None => "<none>".to_string(),
                     }
                 }
-                println!("[debug] Config loaded (raw values as read):");
-                println!("  username: {}", c.username.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  api_key: {}", mask_opt(&c.api_key));
-                println!("  period: {}", c.period.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  limit: {}", c.limit.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  select: {}", c.select.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  format: {}", c.format.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  join: {}", c.join.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  prefix: {}", c.prefix.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  suffix: {}", c.suffix.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  strip_feat: {}", c.strip_feat.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  strip_feat_regex: {}", c.strip_feat_regex.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  copy: {}", c.copy.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  discord_token: {}", mask_opt(&c.discord_token));
-                println!("  discord_bio_regex: {}", c.discord_bio_regex.clone().unwrap_or_else(|| "<none>".into()));
-                println!("  update_discord: {}", c.update_discord.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  discord_dry_run: {}", c.discord_dry_run.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
-                println!("  debug: {}", c.debug.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("[debug] Config loaded (raw values as read):");
+                eprintln!("  username: {}", c.username.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  api_key: {}", mask_opt(&c.api_key));
+                eprintln!("  api_key_file: {}", c.api_key_file.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  api_key_command: {}", c.api_key_command.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  period: {}", c.period.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  limit: {}", c.limit.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  select: {}", c.select.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  format: {}", c.format.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  join: {}", c.join.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  prefix: {}", c.prefix.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  suffix: {}", c.suffix.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  list_template: {}", c.list_template.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  list_template_file: {}", c.list_template_file.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_markdown: {}", c.discord_markdown.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  default_preset: {}", c.default_preset.clone().unwrap_or_else(|| "<none>".into()));
+                let names: Vec<&str> = c.presets.keys().map(String::as_str).collect();
+                eprintln!("  presets: {}", if names.is_empty() { "<none>".to_string() } else { names.join(", ") });
+                eprintln!("  strip_feat: {}", c.strip_feat.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  strip_feat_regex: {}", c.strip_feat_regex.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  transliterate: {}", c.transliterate.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  romanized_names: {}", c.romanized_names.len());
+                eprintln!("  aliases: {}", c.aliases.len());
+                eprintln!("  filters: {}", c.filters.len());
+                eprintln!("  merge: {}", c.merge.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  move_feat: {}", c.move_feat.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  featured_separator: {}", c.featured_separator.clone().unwrap_or_else(|| "<none>".into()));
+                match &c.cleanup {
+                    Some(rules) => {
+                        let names: Vec<String> = rules
+                            .iter()
+                            .map(|r| if r.enabled { r.name.clone() } else { format!("{} (disabled)", r.name) })
+                            .collect();
+                        eprintln!("  cleanup: {}", names.join(", "));
+                    }
+                    None => eprintln!("  cleanup: <none>"),
+                }
+                eprintln!("  copy: {}", c.copy.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  output_format: {}", c.output_format.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  table: {}", c.table.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_token: {}", mask_opt(&c.discord_token));
+                eprintln!("  discord_token_file: {}", c.discord_token_file.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_token_command: {}", c.discord_token_command.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_bio_regex: {}", c.discord_bio_regex.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_bio_limit: {}", c.discord_bio_limit.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  bio_fit: {}", c.bio_fit.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  fallback_format: {}", c.fallback_format.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  update_discord: {}", c.update_discord.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_dry_run: {}", c.discord_dry_run.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_confirm: {}", c.discord_confirm.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_on_conflict: {}", c.discord_on_conflict.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_targets: {}", c.discord_targets.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  status_format: {}", c.status_format.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  status_emoji: {}", c.status_emoji.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  status_expires: {}", c.status_expires.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  pronouns_format: {}", c.pronouns_format.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_guild_ids: {}", c.discord_guild_ids.as_ref().map(|v| v.join(", ")).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  webhook: {}", c.webhook.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  webhook_url: {}", mask_opt(&c.webhook_url));
+                eprintln!("  webhook_embed: {}", c.webhook_embed.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  webhook_username: {}", c.webhook_username.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_bot.token: {}", mask_opt(&c.bot.token));
+                eprintln!("  discord_bot.channel_id: {}", c.bot.channel_id.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_bot.post: {}", c.bot.post.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  discord_bot.about: {}", c.bot.about.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  presence_client_id: {}", c.presence_client_id.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  presence_details: {}", c.presence_details.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  presence_state: {}", c.presence_state.clone().unwrap_or_else(|| "<none>".into()));
+                eprintln!("  presence_interval: {}", c.presence_interval.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
+                eprintln!("  debug: {}", c.debug.map(|v| v.to_string()).unwrap_or_else(|| "<none>".into()));
             }
             None => {
                 if let Some(p) = &found_config_path {
-                    println!("[debug] Config file was found at {} but failed to load (read/parse error). See error above.", p.display());
+                    eprintln!("[debug] Config file was found at {} but failed to load (read/parse error). See error above.", p.display());
                 } else {
-                    println!("[debug] No config file was loaded (using CLI/env defaults)");
+                    eprintln!("[debug] No config file was loaded (using CLI/env defaults)");
                     let locations = crate::config::config_search_locations();
                     for p in locations {
-                        println!("[debug]   searched: {}", p.display());
+                        eprintln!("[debug]   searched: {}", p.display());
                     }
                 }
             }
         }
     }
 
-    // Resolve API key: CLI > env > config
-    let api_key = match cli
-        .api_key
+    // Secrets: an explicit value (CLI > env > config) wins, then *_file, *_command and finally the encrypted store.
+    // The store is only unlocked (prompting for its passphrase) when a value is actually missing.
+    let mut secrets = SecretResolver::default();
+    let discord_token_direct = cli
+        .discord_token
         .clone()
-        .or_else(|| env::var("LASTFM_API_KEY").ok())
-        .or_else(|| cfg.as_ref().and_then(|c| c.api_key.clone()))
-    {
+        .or_else(|| env::var("DISCORD_TOKEN").ok())
+        .or_else(|| cfg.as_ref().and_then(|c| c.discord_token.clone()));
+
+    // Official romanised names apply wherever text is transliterated, including the `romanize` filter.
+    if let Some(c) = cfg.as_ref() {
+        set_romanized_names(c.romanized_names.clone());
+    }
+
+    // Subcommands only need the config and token; handle them before requiring Last.fm credentials.
+    match &cli.command {
+        Some(Command::Secret { action }) => return run_secret_command(action),
+        Some(Command::Config { action: ConfigCommand::Show { origin } }) => {
+            print_settings(&settings, &credential_origins(&cli, cfg.as_ref()), found_config_path.as_deref(), *origin);
+            return Ok(());
+        }
+        // Handled right after the config was loaded
+        Some(Command::Config { action: ConfigCommand::Check | ConfigCommand::Profiles }) => return Ok(()),
+        Some(Command::Text { action: TextCommand::Romanize { text } }) => {
+            println!("{}", romanize_name(text));
+            return Ok(());
+        }
+        Some(Command::Text { action: TextCommand::Test { title } }) => {
+            let cleanup = build_cleanup(&settings, cfg.as_ref());
+            print_cleanup_trace(&cleanup, title);
+            return Ok(());
+        }
+        Some(Command::Discord { action }) => {
+            let dry_run = settings.discord_dry_run;
+            let token = if matches!(action, DiscordCommand::History) {
+                None
+            } else {
+                resolve_discord_token(discord_token_direct.clone(), cfg.as_ref(), &mut secrets)
+            };
+            return run_discord_command(action, token.as_deref(), dry_run, early_debug).await;
+        }
+        None => {}
+    }
+
+    let api_key = match resolve_credential(
+        cli.api_key.clone().or_else(|| env::var("LASTFM_API_KEY").ok()).or_else(|| cfg.as_ref().and_then(|c| c.api_key.clone())),
+        cfg.as_ref().and_then(|c| c.api_key_file.as_deref()),
+        cfg.as_ref().and_then(|c| c.api_key_command.as_deref()),
+        "api_key",
+        &mut secrets,
+    ) {
         Some(k) => k,
         None => {
-            eprintln!("ERROR: Missing Last.fm API key. Pass --api-key, set LASTFM_API_KEY env var, or provide api_key in topsongs.config.kdl.");
+            eprintln!("ERROR: Missing Last.fm API key. Pass --api-key, set LASTFM_API_KEY env var, provide api_key (or api_key_file/api_key_command) in topsongs.config.kdl, or run `topsongs secret set api_key`.");
             std::process::exit(2);
         }
     };
 
-    // Resolve Last.fm username: CLI > config (no env fallback)
-    let username = match cli.username.clone().or_else(|| cfg.as_ref().and_then(|c| c.username.clone())) {
+    let username = match settings.username.clone() {
         Some(u) => u,
         None => {
-            eprintln!("ERROR: Missing Last.fm username. Pass --username or set username in topsongs.config.kdl.");
+            eprintln!("ERROR: Missing Last.fm username. Pass --username, set TOPSONGS_USERNAME or set username in topsongs.config.kdl.");
             std::process::exit(2);
         }
     };
+    if let Some(e) = settings.errors.first() {
+        eprintln!("ERROR: {}", e);
+        std::process::exit(2);
+    }
 
-    // Resolve period with precedence: CLI > config > default Overall
-    let period: crate::cli::Period = if let Some(p) = cli.period {
-        p
-    } else if let Some(pstr) = cfg.as_ref().and_then(|c| c.period.clone()) {
-        match pstr.as_str() {
-            "overall" => crate::cli::Period::Overall,
-            "7day" => crate::cli::Period::SevenDay,
-            "1month" => crate::cli::Period::OneMonth,
-            "3month" => crate::cli::Period::ThreeMonth,
-            "6month" => crate::cli::Period::SixMonth,
-            "12month" => crate::cli::Period::TwelveMonth,
-            _ => crate::cli::Period::Overall,
-        }
-    } else {
-        crate::cli::Period::Overall
-    };
+    let Settings {
+        period,
+        limit,
+        select: select_opt,
+        format,
+        join,
+        prefix,
+        suffix,
+        discord_markdown,
+        move_feat,
+        merge,
+        transliterate,
+        featured_separator,
+        copy,
+        debug,
+        table,
+        output_format,
+        discord_bio_regex,
+        bio_limit,
+        bio_fit,
+        fallback_format,
+        update_discord,
+        discord_dry_run,
+        discord_confirm,
+        on_conflict,
+        discord_targets,
+        status_format,
+        status_emoji,
+        status_expires,
+        pronouns_format,
+        guild_ids,
+        webhook: post_to_webhook,
+        webhook_embed,
+        webhook_username,
+        bot_channel,
+        bot_post,
+        bot_about,
+        presence_client_id,
+        presence_details,
+        presence_state,
+        ..
+    } = settings.clone();
+    let presence_interval = settings.presence_interval.max(15);
 
-    // numeric options
-    let mut limit = cli.limit;
-    if limit == 10 {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.limit) { limit = v; }
+    // Inline templates get escape handling; files are read as they are.
+    let list_template = settings.list_template.as_ref().map(|source| match source {
+        ListTemplateSource::Inline(inline) => interpret_escapes(inline),
+        ListTemplateSource::File(path) => read_list_template(path),
+    });
+    let templates = [
+        ("format", Some(&format)),
+        ("join", Some(&join)),
+        ("prefix", Some(&prefix)),
+        ("suffix", Some(&suffix)),
+        ("list_template", list_template.as_ref()),
+        ("fallback_format", fallback_format.as_ref()),
+        ("status_format", Some(&status_format)),
+        ("pronouns_format", Some(&pronouns_format)),
+        ("presence_details", Some(&presence_details)),
+        ("presence_state", Some(&presence_state)),
+    ];
+    for (name, tpl) in templates {
+        for problem in tpl.map(|t| crate::template::check(t)).unwrap_or_default() {
+            eprintln!("Warning: {}: {}", name, problem);
+        }
     }
-    // Determine selection preference: CLI overrides config; if None, we'll use interactive selection
-    let select_opt: Option<usize> = cli.select.or_else(|| cfg.as_ref().and_then(|c| c.select));
 
-    // strings with defaults
-    let mut format = cli.format.clone();
-    if format == "  - {artist} - {track}" {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.format.clone()) { format = v; }
-    }
-    let mut join = cli.join.clone();
-    if join == "\n" {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.join.clone()) { join = v; }
-    }
-    let mut prefix = cli.prefix.clone();
-    if prefix.is_empty() {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.prefix.clone()) { prefix = v; }
-    }
-    let mut suffix = cli.suffix.clone();
-    if suffix.is_empty() {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.suffix.clone()) { suffix = v; }
-    }
+    let cleanup = build_cleanup(&settings, cfg.as_ref());
+    let aliases = build_aliases(cfg.as_ref());
+    let filters = build_filters(&cli, cfg.as_ref());
+    let show_aliases_applied = cli.show_aliases_applied;
+    // Machine-readable formats keep stdout for the data itself
+    let machine_output = output_format != OutputFormat::Text;
 
-    // booleans
-    let mut strip_feat = cli.strip_feat;
-    if !strip_feat {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.strip_feat) { strip_feat = v; }
-    }
-    let mut copy = cli.copy;
-    if !copy {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.copy) { copy = v; }
-    }
-    let mut debug = cli.debug;
-    if !debug {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.debug) { debug = v; }
-    }
+    // Webhook sink
+    let webhook_url = if post_to_webhook {
+        let direct = cli
+            .webhook_url
+            .clone()
+            .or_else(|| env::var("DISCORD_WEBHOOK_URL").ok())
+            .or_else(|| cfg.as_ref().and_then(|c| c.webhook_url.clone()));
+        resolve_credential(direct, None, None, "webhook_url", &mut secrets)
+    } else {
+        None
+    };
 
+    // Bot/application mode; deliberately independent of the personal discord_token
+    let bot_token = if bot_post || bot_about {
+        let direct = cli
+            .bot_token
+            .clone()
+            .or_else(|| env::var("DISCORD_BOT_TOKEN").ok())
+            .or_else(|| cfg.as_ref().and_then(|c| c.bot.token.clone()));
+        resolve_credential(direct, None, None, "discord_bot_token", &mut secrets)
+    } else {
+        None
+    };
 
-    let mut strip_feat_regex = cli.strip_feat_regex.clone();
-    if strip_feat_regex.is_none() {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.strip_feat_regex.clone()) { strip_feat_regex = Some(v); }
-    }
+    let pipeline = TrackPipeline {
+        aliases: &aliases,
+        filters: &filters,
+        cleanup: &cleanup,
+        featured_separator: &featured_separator,
+        move_feat,
+        transliterate,
+        merge,
+    };
+    // Filters and merging shrink the list, so fetch more until enough tracks remain or Last.fm runs out.
+    let wanted = (limit as usize).max(select_opt.unwrap_or(0));
+    let mut fetch_limit = limit;
+    let (tracks, prepared) = loop {
+        let tracks = fetch_top_tracks(&username, &api_key, period.as_api_value(), fetch_limit, debug)
+            .await
+            .with_context(|| "Failed to fetch top tracks from Last.fm")?;
+        // --query shows Last.fm's data untouched.
+        if cli.query || tracks.is_empty() {
+            break (tracks, None);
+        }
+        let prepared = pipeline.run(tracks.clone());
+        let exhausted = tracks.len() < fetch_limit as usize || fetch_limit >= LASTFM_MAX_LIMIT;
+        if prepared.tracks.len() >= wanted || exhausted {
+            break (tracks, Some(prepared));
+        }
+        fetch_limit = fetch_limit.saturating_mul(2).min(LASTFM_MAX_LIMIT);
+        if debug {
+            eprintln!("[debug] {} of {} tracks left after filtering and merging; fetching {}", prepared.tracks.len(), tracks.len(), fetch_limit);
+        }
+    };
 
-    let mut discord_bio_regex = cli.discord_bio_regex.clone();
-    if discord_bio_regex == r"/\*\*[\w ]+\*\*:?\r?(\n[ \w-]+)+\n/" {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.discord_bio_regex.clone()) { discord_bio_regex = v; }
+    if tracks.is_empty() {
+        status!(machine_output, "No tracks found. Check username or try a different period.");
+        return Ok(());
     }
 
-    let mut update_discord = cli.update_discord;
-    if !update_discord {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.update_discord) { update_discord = v; }
-    }
-    let mut discord_dry_run = cli.discord_dry_run;
-    if !discord_dry_run {
-        if let Some(v) = cfg.as_ref().and_then(|c| c.discord_dry_run) { discord_dry_run = v; }
+    // If in query mode, we only show the fetched list and exit.
+    if cli.query
+        && let Some(formatted) = format_tracks(
+            &tracks,
+            output_format,
+            &ListMeta { username: &username, period: period.as_api_value(), rendered: None },
+        )
+    {
+        print!("{}", formatted);
+        return Ok(());
     }
 
-    // Resolve Discord token: CLI > env > config
-    let discord_token_opt = cli
-        .discord_token
-        .clone()
-        .or_else(|| env::var("DISCORD_TOKEN").ok())
-        .or_else(|| cfg.as_ref().and_then(|c| c.discord_token.clone()));
-
-    let tracks = fetch_top_tracks(
-        &username,
-        &api_key,
-        period.as_api_value(),
-        limit,
-        debug,
-    )
-    .await
-        .with_context(|| "Failed to fetch top tracks from Last.fm")?;
-
+    let mut filtered_out = 0;
+    let tracks: Vec<Track> = match prepared {
+        None => tracks,
+        Some(mut prepared) => {
+            if show_aliases_applied {
+                let report = if prepared.aliases_applied.is_empty() {
+                    format!("No aliases applied ({} configured).", aliases.len())
+                } else {
+                    format!("Aliases applied:\n{}", prepared.aliases_applied.join("\n"))
+                };
+                status!(machine_output, "{}\n", report);
+            }
+            filtered_out = prepared.filtered_out;
+            prepared.tracks.truncate(wanted);
+            prepared.tracks
+        }
+    };
     if tracks.is_empty() {
-        println!("No tracks found. Check username or try a different period.");
+        status!(machine_output, "No tracks left after filtering ({} filtered out). Check the include/exclude filters.", filtered_out);
         return Ok(());
     }
+    let merged_label = |t: &Track| if t.merged > 0 { format!(" [{} entries merged]", t.merged + 1) } else { String::new() };
 
-    println!("Top {} tracks for '{}' (period: {}):", tracks.len(), username, period.as_api_value());
-    for (idx, t) in tracks.iter().enumerate() {
-        let pc = t.playcount.parse::<u32>().unwrap_or(0);
-        println!("{:>2}. {} — {} ({} plays)", idx + 1, t.artist.name, t.name, pc);
+    let filtered_note = if filtered_out > 0 { format!(", {} filtered out", filtered_out) } else { String::new() };
+    let mut listing = format!("Top {} tracks for '{}' (period: {}{}):", tracks.len(), username, period.as_api_value(), filtered_note);
+    if table {
+        listing.push('\n');
+        listing.push_str(&aligned_table(&tracks));
+    } else {
+        for (idx, t) in tracks.iter().enumerate() {
+            let pc = t.playcount.parse::<u32>().unwrap_or(0);
+            listing.push_str(&format!("\n{:>2}. {} — {} ({} plays){}", idx + 1, t.artist.name, t.name, pc, merged_label(t)));
+        }
     }
+    status!(machine_output, "{}", listing);
 
-    // If in query mode, we only show the fetched list and exit.
     if cli.query {
         return Ok(());
     }
@@ -411,13 +677,13 @@ async fn main() -> Result<()> {
     let chosen: Vec<&Track> = if let Some(mut n) = select_opt {
         if n == 0 { n = 1; }
         if n > tracks.len() { n = tracks.len(); }
-        println!("\nAuto-selecting top {} track(s).", n);
+        status!(machine_output, "\nAuto-selecting top {} track(s).", n);
         tracks.iter().take(n).collect()
     } else {
         let items: Vec<String> = tracks.iter().enumerate().map(|(i, t)| {
             let pc = t.playcount.parse::<u32>().unwrap_or(0);
             // Prefix with list index to aid selection
-            format!("{:02}) {} — {} ({} plays)", i + 1, t.artist.name, t.name, pc)
+            format!("{:02}) {} — {} ({} plays){}", i + 1, t.artist.name, t.name, pc, merged_label(t))
         }).collect();
         // Use Cursive-based ordered selection (compact dialog) to preserve the order you pick items
         let indices = crate::ui::select_ordered_with_cursive(items)?;
@@ -427,80 +693,914 @@ async fn main() -> Result<()> {
         }
         indices.into_iter().map(|i| &tracks[i]).collect()
     };
-
-    let rendered: Vec<String> = chosen
-        .into_iter()
-        .map(|t| {
-            let title = if strip_feat {
-                strip_title(&t.name, strip_feat_regex.as_deref())
-            } else {
-                t.name.clone()
-            };
-            let mut temp = t.clone();
-            temp.name = title;
-            render_template(&format, &temp)
-        })
-        .collect();
+    let prepared: Vec<Track> = chosen.into_iter().cloned().collect();
 
     // Interpret backslash escape sequences in join/prefix/suffix so that, e.g., "\\n" becomes a real newline.
     let join_str = interpret_escapes(&join);
     let prefix_i = interpret_escapes(&prefix);
     let suffix_i = interpret_escapes(&suffix);
 
-    let list = rendered.join(&join_str);
-    let output = format!("{}{}{}", prefix_i, list, suffix_i);
-    println!("\nYour Discord bio line:\n{}", output);
+    let list_context = ListContext {
+        username: &username,
+        period: period.as_api_value(),
+        date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+    };
+    let output = match list_template.as_deref() {
+        Some(tpl) => render_list_template(tpl, &prepared, &format, &list_context, discord_markdown),
+        None => render_list(&prepared, &format, &join_str, &prefix_i, &suffix_i, discord_markdown),
+    };
+    match format_tracks(
+        &prepared,
+        output_format,
+        &ListMeta { username: &username, period: period.as_api_value(), rendered: Some(&output) },
+    ) {
+        Some(formatted) => print!("{}", formatted),
+        None => println!("\nYour Discord bio line:\n{}", output),
+    }
 
     if copy {
         if let Err(e) = copy_to_clipboard(&output) {
             eprintln!("Failed to copy to clipboard: {}", e);
         } else {
-            println!("Copied to clipboard.");
+            status!(machine_output, "Copied to clipboard.");
         }
     }
 
+    // Shared by every destination that has to fit the list into a length limit
+    let fit_input = FitInput {
+        tracks: &prepared,
+        format: &format,
+        fallback_format: fallback_format.as_deref(),
+        join: &join_str,
+        prefix: &prefix_i,
+        suffix: &suffix_i,
+        list_template: list_template.as_deref().map(|tpl| (tpl, &list_context)),
+        markdown: discord_markdown,
+    };
+
+    let chart_title = format!("Top tracks for {} ({})", username, period.as_api_value());
+    if post_to_webhook {
+        match webhook_url.as_deref() {
+            Some(url) => {
+                let msg = WebhookMessage {
+                    tracks: &prepared,
+                    rendered: &output,
+                    embed: webhook_embed,
+                    title: &chart_title,
+                    username: webhook_username.as_deref(),
+                };
+                match build_payload(&msg) {
+                    Ok(payload) if discord_dry_run => {
+                        let pretty = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string());
+                        status!(machine_output, "\n[Webhook dry-run] Would post:\n{}", pretty);
+                    }
+                    Ok(payload) => match post_webhook(url, &payload, debug).await {
+                        Ok(()) => status!(machine_output, "Posted to Discord webhook."),
+                        Err(e) => eprintln!("Failed to post to Discord webhook: {}", e),
+                    },
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            None => eprintln!("Webhook posting requested but no URL provided. Use --webhook-url, set DISCORD_WEBHOOK_URL, provide webhook_url in config, or run `topsongs secret set webhook_url`."),
+        }
+    }
+
+    if bot_post || bot_about {
+        match bot_token.as_deref() {
+            Some(bot_token) => {
+                run_bot_operations(&BotOperations {
+                    token: bot_token,
+                    post_channel: if bot_post { Some(bot_channel.as_deref()) } else { None },
+                    about: bot_about,
+                    message: WebhookMessage {
+                        tracks: &prepared,
+                        rendered: &output,
+                        embed: webhook_embed,
+                        title: &chart_title,
+                        username: None,
+                    },
+                    fit_input: &fit_input,
+                    bio_fit: &bio_fit,
+                    machine_output,
+                }, discord_dry_run, debug).await;
+            }
+            None => eprintln!("Bot operations requested but no bot token provided. Use --bot-token, set DISCORD_BOT_TOKEN, add a token to the discord_bot block in config, or run `topsongs secret set discord_bot_token`."),
+        }
+    }
+
+    // Ask before sending unless --yes or `discord_confirm #false`. Without a terminal (cron, scripts) there is
+    // nobody to ask, so the update goes ahead as it did before confirmation existed.
+    let confirm_update = discord_confirm && std::io::stdin().is_terminal();
+    if discord_confirm && !confirm_update && update_discord && !discord_dry_run {
+        eprintln!("Note: stdin is not a terminal, so the bio diff is not confirmed before updating. Pass --yes to silence this note.");
+    }
+
     // Discord operations are executed only when explicitly requested.
     let do_discord = update_discord || discord_dry_run;
+    let discord_token_opt = if do_discord { resolve_discord_token(discord_token_direct, cfg.as_ref(), &mut secrets) } else { None };
     if do_discord {
         if let Some(token) = discord_token_opt.as_deref() {
-            match get_current_bio(token, debug).await {
-                Ok(current_bio) => {
-                    let pattern = normalize_pattern(&discord_bio_regex);
-                    let re = match Regex::new(&pattern) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            eprintln!("Invalid regex for --discord-bio-regex: {}", e);
-                            return Ok(());
-                        }
-                    };
-
-                    if let Some(_m) = re.find(&current_bio) {
-                        let replacement = format!("{}\n", output);
-                        let new_bio = re.replace(&current_bio, replacement.as_str()).to_string();
-
-                        if discord_dry_run {
-                            println!("\n[Discord dry-run] Would update bio to:\n{}", new_bio);
-                            println!("[Discord dry-run] No changes were sent to Discord.");
-                        } else if update_discord {
-                            if new_bio == current_bio {
-                                println!("Discord bio is already up to date. No update sent.");
-                            } else {
-                                match update_bio(token, &new_bio, debug).await {
-                                    Ok(()) => println!("Discord bio updated successfully."),
-                                    Err(e) => eprintln!("Failed to update Discord bio: {}", e),
+            if discord_targets.contains(&DiscordTarget::Bio) {
+                match get_profile(token, debug).await {
+                    Ok(DiscordProfile { id: account, username, bio: current_bio }) => {
+                        let pattern = normalize_pattern(&discord_bio_regex);
+                        let re = match Regex::new(&pattern) {
+                            Ok(r) => r,
+                            Err(e) => {
+                                eprintln!("Invalid regex for --discord-bio-regex: {}", e);
+                                return Ok(());
+                            }
+                        };
+
+                        if let Some(_m) = re.find(&current_bio) {
+                            // Replace the matched section of `base` with the list, auto-fitting against the whole bio.
+                            let build_bio = |base: &str| {
+                                let compose = |list: &str| re.replace(base, NoExpand(&format!("{}\n", list))).to_string();
+                                let fitted = fit_to_limit(&fit_input, bio_limit, &bio_fit, |list| bio_length(&compose(list)));
+                                let new_bio = compose(&fitted.output);
+                                (fitted, new_bio)
+                            };
+                            let (fitted, new_bio) = build_bio(&current_bio);
+
+                            let matches: Vec<_> = re.find_iter(&current_bio).map(|m| m.range()).collect();
+                            let notes = match_notes(&current_bio, &matches, &prefix_i);
+                            for warning in &fitted.warnings {
+                                eprintln!("Warning: {}", warning);
+                            }
+
+                            if discord_dry_run {
+                                status!(machine_output, "\n[Discord dry-run] Changes to your bio:");
+                                status!(machine_output, "{}", render_bio_diff(&current_bio, &new_bio).trim_end_matches('\n'));
+                                status!(machine_output, "[Discord dry-run] {}", length_summary(&current_bio, &new_bio, bio_limit));
+                                for note in &notes {
+                                    status!(machine_output, "[Discord dry-run] {}", note);
+                                }
+                                for note in &fitted.applied {
+                                    status!(machine_output, "[Discord dry-run] Auto-fit: {}", note);
+                                }
+                                if !fitted.fits {
+                                    status!(machine_output, "[Discord dry-run] The bio is still over the limit; a real update would be refused. Try --bio-fit drop,truncate or a shorter --format.");
+                                }
+                                status!(machine_output, "[Discord dry-run] No changes were sent to Discord.");
+                            } else if update_discord {
+                                if !fitted.fits {
+                                    eprintln!(
+                                        "New Discord bio is {} characters, over the limit of {}. No update sent. Use --bio-fit to shrink it automatically.",
+                                        fitted.length, bio_limit
+                                    );
+                                } else if new_bio == current_bio {
+                                    status!(machine_output, "Discord bio is already up to date. No update sent.");
+                                } else if confirm_update && !confirm_bio_change(&current_bio, &new_bio, bio_limit, &notes, &fitted.applied, machine_output) {
+                                    status!(machine_output, "Discord update cancelled. No changes were sent.");
+                                } else {
+                                    if !confirm_update {
+                                        for note in &fitted.applied {
+                                            status!(machine_output, "Auto-fit: {}", note);
+                                        }
+                                    }
+                                    // Re-fetch right before the PATCH: selection and confirmation may have taken minutes,
+                                    // and the bio could have been edited in the Discord client meanwhile.
+                                    let to_send = match get_current_bio(token, debug).await {
+                                        Ok(latest) => {
+                                            match resolve_conflict(&current_bio, &latest, &new_bio, on_conflict, &re, bio_limit, |bio| build_bio(bio).1) {
+                                                ConflictOutcome::Send { base, bio } => Some((base, bio)),
+                                                ConflictOutcome::Reapplied { base, bio } => {
+                                                    status!(machine_output, "Your Discord bio changed since it was fetched; re-applied the replacement to the latest version.");
+                                                    Some((base, bio))
+                                                }
+                                                ConflictOutcome::UpToDate => {
+                                                    status!(machine_output, "Your Discord bio changed since it was fetched and already contains this list. No update sent.");
+                                                    None
+                                                }
+                                                ConflictOutcome::Abort(reason) => {
+                                                    eprintln!("{}", reason);
+                                                    None
+                                                }
+                                            }
+                                        }
+                                        Err(e) => {
+                                            eprintln!("Failed to re-check the current Discord bio before updating, so no update was sent: {}", e);
+                                            None
+                                        }
+                                    };
+                                    if let Some((base_bio, bio_to_send)) = to_send {
+                                        match record_backup(&account, &username, &base_bio) {
+                                            Ok(id) => match update_bio(token, &bio_to_send, debug).await {
+                                                Ok(()) => status!(machine_output, "Discord bio updated successfully. Previous bio saved as backup #{} (undo with `topsongs discord undo`).", id),
+                                                Err(e) => eprintln!("Failed to update Discord bio: {}", e),
+                                            },
+                                            Err(e) => eprintln!("Failed to back up the current Discord bio, so no update was sent: {:#}", e),
+                                        }
+                                    }
                                 }
                             }
+                        } else {
+                            eprintln!("The provided regex did not match your current Discord bio. No update performed.");
                         }
-                    } else {
-                        eprintln!("The provided regex did not match your current Discord bio. No update performed.");
                     }
+                    Err(e) => eprintln!("Failed to fetch current Discord bio: {}", e),
                 }
-                Err(e) => eprintln!("Failed to fetch current Discord bio: {}", e),
             }
+
+            let fields = ProfileFields {
+                targets: &discord_targets,
+                status_format: &status_format,
+                status_emoji: status_emoji.as_deref(),
+                status_expires: status_expires.as_deref(),
+                pronouns_format: &pronouns_format,
+                guild_ids: &guild_ids,
+                bio_limit,
+                bio_fit: &bio_fit,
+                machine_output,
+            };
+            update_profile_fields(&fields, &fit_input, token, discord_dry_run, confirm_update, debug).await;
         } else {
-            eprintln!("Discord operations requested but no token provided. Use --discord-token, set DISCORD_TOKEN, or provide discord_token in config.");
+            eprintln!("Discord operations requested but no token provided. Use --discord-token, set DISCORD_TOKEN, run `topsongs secret set discord_token`, or set discord_token_file/discord_token_command in config.");
+        }
+    }
+
+    if cli.presence {
+        let Some(client_id) = presence_client_id.as_deref() else {
+            eprintln!("Rich Presence requires a Discord application id. Use --presence-client-id or provide presence_client_id in config.");
+            std::process::exit(2);
+        };
+        // Discord rejects activity text under two characters; such fields are left out, and tracks with neither are skipped.
+        let lines: Vec<(String, String)> = prepared
+            .iter()
+            .map(|t| (render_template(&presence_details, t), render_template(&presence_state, t)))
+            .filter(|(details, state)| activity_text(details).is_some() || activity_text(state).is_some())
+            .collect();
+        if lines.is_empty() {
+            eprintln!("Rich Presence has nothing to show: presence_details and presence_state render to less than 2 characters for every track.");
+            std::process::exit(2);
+        }
+        if discord_dry_run {
+            for (details, state) in &lines {
+                status!(machine_output, "[Presence dry-run] Would show: {} | {}", details, state);
+            }
+        } else {
+            run_presence(client_id, &lines, presence_interval, machine_output).await?;
         }
     }
 
     Ok(())
 }
+
+struct BotOperations<'a> {
+    token: &'a str,
+    /// `Some` when posting was requested; the inner value is the configured channel, if any
+    post_channel: Option<Option<&'a str>>,
+    about: bool,
+    message: WebhookMessage<'a>,
+    fit_input: &'a FitInput<'a>,
+    bio_fit: &'a [FitStrategy],
+    machine_output: bool,
+}
+
+/// Post to a channel and/or update the bot's About Me using the bot token. Each operation reports its own failure.
+async fn run_bot_operations(ops: &BotOperations<'_>, dry_run: bool, debug: bool) {
+    match ops.post_channel {
+        Some(Some(channel_id)) if !is_snowflake(channel_id) => {
+            eprintln!("Invalid channel id '{}': expected the numeric channel id. Nothing posted.", channel_id)
+        }
+        Some(Some(channel_id)) => match build_payload(&ops.message) {
+            Ok(payload) if dry_run => {
+                let pretty = serde_json::to_string_pretty(&payload).unwrap_or_else(|_| payload.to_string());
+                status!(ops.machine_output, "\n[Bot dry-run] Would post to channel {}:\n{}", channel_id, pretty);
+            }
+            Ok(payload) => match post_channel_message(ops.token, channel_id, &payload, debug).await {
+                Ok(()) => status!(ops.machine_output, "Posted to channel {} as the bot.", channel_id),
+                Err(e) => eprintln!("Failed to post to channel {} as the bot: {}", channel_id, e),
+            },
+            Err(e) => eprintln!("{}", e),
+        },
+        Some(None) => eprintln!("--bot-post needs a channel. Use --bot-channel or set channel_id in the discord_bot block."),
+        None => {}
+    }
+
+    if ops.about {
+        let fitted = fit_to_limit(ops.fit_input, BOT_ABOUT_LIMIT, ops.bio_fit, bio_length);
+        for warning in &fitted.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        for note in &fitted.applied {
+            status!(ops.machine_output, "Bot About Me auto-fit: {}", note);
+        }
+        if !fitted.fits {
+            eprintln!(
+                "Bot About Me would be {} characters, over the limit of {}. Not updated. Use --bio-fit to shrink it automatically.",
+                fitted.length, BOT_ABOUT_LIMIT
+            );
+        } else if dry_run {
+            status!(ops.machine_output, "[Bot dry-run] Would set the bot's About Me to:\n{}", fitted.output);
+        } else {
+            match update_bot_about(ops.token, &fitted.output, debug).await {
+                Ok(()) => status!(ops.machine_output, "Bot About Me updated."),
+                Err(e) => eprintln!("Failed to update the bot's About Me: {}", e),
+            }
+        }
+    }
+}
+
+/// Rotate through `lines` (details, state) as Rich Presence until Ctrl+C, then clear the activity.
+/// Lost connections (e.g. Discord restarted) are retried on the next tick.
+async fn run_presence(client_id: &str, lines: &[(String, String)], interval_secs: u64, machine_output: bool) -> Result<()> {
+    let mut client = Some(crate::presence::connect(client_id).await.with_context(|| "Failed to connect to Discord for Rich Presence")?);
+    status!(machine_output, "\nShowing Rich Presence every {}s. Press Ctrl+C to stop.", interval_secs);
+    let interval = std::time::Duration::from_secs(interval_secs);
+    for (details, state) in lines.iter().cycle() {
+        if client.is_none() {
+            client = crate::presence::connect(client_id).await.ok();
+        }
+        if let Some(c) = client.as_mut() {
+            match c.set_activity(details, state).await {
+                Ok(()) => status!(machine_output, "Presence: {} | {}", details, state),
+                Err(e) => {
+                    eprintln!("Failed to update Rich Presence: {}", e);
+                    client = None;
+                }
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    if let Some(c) = client.as_mut()
+        && let Err(e) = c.clear_activity().await
+    {
+        eprintln!("Failed to clear Rich Presence: {}", e);
+    }
+    Ok(())
+}
+
+/// Build the title cleanup pipeline. A `cleanup` block in the config defines the rules; otherwise the
+/// legacy strip_feat/strip_feat_regex settings become a single `feat` rule. Invalid rules are config errors.
+fn build_cleanup(settings: &Settings, cfg: Option<&crate::config::Config>) -> Cleanup {
+    let feat_regex = settings.strip_feat_regex.clone();
+    let specs: Vec<RuleSpec> = match cfg.and_then(|c| c.cleanup.clone()) {
+        Some(mut rules) => {
+            for rule in rules.iter_mut().filter(|r| r.name == "feat" && r.pattern.is_none()) {
+                rule.pattern = feat_regex.clone();
+            }
+            rules
+        }
+        None => vec![RuleSpec {
+            name: "feat".to_string(),
+            enabled: settings.strip_feat,
+            pattern: feat_regex,
+            replace: None,
+        }],
+    };
+    match Cleanup::from_specs(&specs) {
+        Ok(cleanup) => cleanup,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("Config error: {}", e);
+            }
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Last.fm's maximum page size for user.getTopTracks; over-fetching for filters stops here.
+const LASTFM_MAX_LIMIT: u32 = 1000;
+
+/// Everything done to the fetched tracks before listing and selection, in order: aliases, featured-artist
+/// extraction, include/exclude filters, title cleanup, transliteration and merging of duplicates.
+struct TrackPipeline<'a> {
+    aliases: &'a Aliases,
+    filters: &'a TrackFilters,
+    cleanup: &'a Cleanup,
+    featured_separator: &'a str,
+    move_feat: bool,
+    transliterate: bool,
+    merge: bool,
+}
+
+struct PreparedTracks {
+    tracks: Vec<Track>,
+    /// One line per alias that rewrote a name, for --show-aliases-applied
+    aliases_applied: Vec<String>,
+    filtered_out: usize,
+}
+
+impl TrackPipeline<'_> {
+    fn run(&self, raw: Vec<Track>) -> PreparedTracks {
+        let mut keys = Vec::with_capacity(raw.len());
+        let mut aliases_applied = Vec::new();
+        let mut filtered_out = 0;
+        let mut alias = |field: AliasField, value: &mut String| {
+            if let Some((replaced, pattern)) = self.aliases.apply(field, value) {
+                aliases_applied.push(format!("  {} {:?} -> {:?}  (alias {:?})", field.as_str(), value, replaced, pattern));
+                *value = replaced;
+            }
+        };
+        let mut prepared = Vec::with_capacity(raw.len());
+        for mut t in raw {
+            // Aliases fix Last.fm's metadata first, so everything below (and merging) sees the corrected names.
+            alias(AliasField::Artist, &mut t.artist.name);
+            alias(AliasField::Track, &mut t.name);
+            // Featured artists are read from the raw title, before cleanup rules may strip the credit.
+            let (without_feat, mut featured) = extract_featured(&t.name, &t.artist.name);
+            for name in featured.iter_mut() {
+                alias(AliasField::Artist, name);
+            }
+            let cleaned = self.cleanup.apply(&t.name);
+            let artists: Vec<&str> = std::iter::once(t.artist.name.as_str()).chain(featured.iter().map(String::as_str)).collect();
+            if !self.filters.keep(&artists, &[&t.name, &cleaned]) {
+                filtered_out += 1;
+                continue;
+            }
+            keys.push(format!("{}\u{0}{}", normalize_key(&t.artist.name), title_key(&self.cleanup.apply(&without_feat))));
+            let mut temp = t.clone();
+            // Artists are transliterated one name at a time so `romanized_names` overrides match whole names.
+            if self.transliterate {
+                temp.artist.name = romanize_name(&temp.artist.name);
+                featured = featured.iter().map(|n| romanize_name(n)).collect();
+            }
+            if !featured.is_empty() {
+                temp.featured = featured.join(self.featured_separator);
+                temp.artists = format!("{}{}{}", temp.artist.name, self.featured_separator, temp.featured);
+                if self.move_feat {
+                    temp.name = without_feat;
+                    temp.artist.name = temp.artists.clone();
+                }
+            }
+            temp.name = self.cleanup.apply(&temp.name);
+            if self.transliterate {
+                temp.name = romanize(&temp.name);
+            }
+            prepared.push(temp);
+        }
+        let tracks = if self.merge { merge_duplicates(prepared, &keys) } else { prepared };
+        PreparedTracks { tracks, aliases_applied, filtered_out }
+    }
+}
+
+fn build_filters(cli: &Cli, cfg: Option<&crate::config::Config>) -> TrackFilters {
+    let mut specs = cfg.map(|c| c.filters.clone()).unwrap_or_default();
+    // Config patterns are checked on their own so a bad flag isn't reported as a config error.
+    let mut failed = false;
+    if let Err(errors) = TrackFilters::from_specs(&specs) {
+        for e in errors {
+            eprintln!("Config error: {}", e);
+        }
+        failed = true;
+    }
+    let flags = [
+        ("--include-artist", FilterAction::Include, FilterField::Artist, &cli.include_artist),
+        ("--include-track", FilterAction::Include, FilterField::Track, &cli.include_track),
+        ("--exclude-artist", FilterAction::Exclude, FilterField::Artist, &cli.exclude_artist),
+        ("--exclude-track", FilterAction::Exclude, FilterField::Track, &cli.exclude_track),
+    ];
+    for (flag, action, field, patterns) in flags {
+        for pattern in patterns {
+            let spec = FilterSpec { action, field, pattern: pattern.clone() };
+            if let Err(errors) = TrackFilters::from_specs(std::slice::from_ref(&spec)) {
+                for e in errors {
+                    eprintln!("ERROR: {}: {}", flag, e);
+                }
+                failed = true;
+            }
+            specs.push(spec);
+        }
+    }
+    if failed {
+        std::process::exit(2);
+    }
+    TrackFilters::from_specs(&specs).unwrap_or_default()
+}
+
+fn build_aliases(cfg: Option<&crate::config::Config>) -> Aliases {
+    let specs = cfg.map(|c| c.aliases.as_slice()).unwrap_or_default();
+    match Aliases::from_specs(specs) {
+        Ok(aliases) => aliases,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("Config error: {}", e);
+            }
+            std::process::exit(2);
+        }
+    }
+}
+
+/// `topsongs config show`: one line per effective setting, optionally with where it came from.
+fn print_settings(settings: &Settings, credentials: &[SettingOrigin], config_path: Option<&std::path::Path>, with_origin: bool) {
+    match config_path {
+        Some(p) => println!("Config file: {}", p.display()),
+        None => println!("Config file: none found"),
+    }
+    let entries: Vec<&SettingOrigin> = settings.origins.iter().chain(credentials).collect();
+    let key_width = entries.iter().map(|e| e.key.len()).max().unwrap_or(0);
+    let value_width = entries.iter().map(|e| display_width(&e.value)).max().unwrap_or(0).min(48);
+    for e in entries {
+        if with_origin {
+            println!("{:<kw$}  {}  {}", e.key, pad_to_width(&e.value, value_width, Align::Left), e.origin, kw = key_width);
+        } else {
+            println!("{:<kw$}  {}", e.key, e.value, kw = key_width);
+        }
+    }
+    for e in &settings.errors {
+        println!("Error: {}", e);
+    }
+}
+
+/// `topsongs config profiles`: each profile with its Last.fm user and the keys it sets; `*` marks the one
+/// selected by --profile/TOPSONGS_PROFILE.
+fn print_profiles(cfg: Option<&config::Config>, config_path: Option<&std::path::Path>, selected: Option<&str>) {
+    let (Some(cfg), Some(path)) = (cfg, config_path) else {
+        println!("No config file loaded, so no profiles are defined.");
+        return;
+    };
+    if cfg.profiles.is_empty() {
+        println!("No profiles defined in {}. Add `profile \"name\" {{ ... }}` blocks to create some.", path.display());
+        return;
+    }
+    println!("Profiles in {}:", path.display());
+    let name_width = cfg.profiles.iter().map(|p| display_width(&p.name)).max().unwrap_or(0);
+    let user_width = cfg.profiles.iter().map(|p| p.username.as_deref().map_or(1, display_width)).max().unwrap_or(0);
+    for p in &cfg.profiles {
+        let marker = if selected == Some(p.name.as_str()) { '*' } else { ' ' };
+        let user = p.username.as_deref().unwrap_or("-");
+        println!(
+            "{} {}  {}  {}",
+            marker,
+            pad_to_width(&p.name, name_width, Align::Left),
+            pad_to_width(user, user_width, Align::Left),
+            p.keys.join(", ")
+        );
+    }
+}
+
+fn print_cleanup_trace(cleanup: &Cleanup, title: &str) {
+    println!("Input:  {:?}", title);
+    let width = cleanup.rules.iter().map(|r| r.name.chars().count()).max().unwrap_or(0);
+    let mut previous = title.to_string();
+    for (rule, after) in cleanup.trace(title) {
+        let effect = match after {
+            None => "(disabled)".to_string(),
+            Some(after) if after == previous => "(no change)".to_string(),
+            Some(after) => {
+                let shown = format!("-> {:?}", after);
+                previous = after;
+                shown
+            }
+        };
+        println!("  {:<width$}  {}", rule.name, effect, width = width);
+    }
+    if cleanup.rules.is_empty() {
+        println!("  (no cleanup rules configured)");
+    }
+    println!("Result: {:?}", cleanup.apply(title));
+}
+
+/// Read a whole-list template file. Relative paths are tried in the current directory, then in the config dir.
+/// A missing or unreadable file is a user error.
+fn read_list_template(path: &str) -> String {
+    let expanded = crate::config::expand_home(path);
+    let candidates = if expanded.is_relative() {
+        vec![expanded.clone(), crate::config::config_dir().join(&expanded)]
+    } else {
+        vec![expanded.clone()]
+    };
+    for candidate in &candidates {
+        if let Ok(content) = std::fs::read_to_string(candidate) {
+            return content.strip_prefix('\u{feff}').unwrap_or(&content).to_string();
+        }
+    }
+    eprintln!("ERROR: Could not read list template file {}", expanded.display());
+    std::process::exit(2);
+}
+
+/// Pick the first available source for a credential: `direct` (CLI/env/config value), then a file,
+/// then a command, then the encrypted store. File/command failures are reported and skipped.
+fn resolve_credential(
+    direct: Option<String>,
+    file: Option<&str>,
+    command: Option<&str>,
+    store_key: &str,
+    secrets: &mut SecretResolver,
+) -> Option<String> {
+    if let Some(v) = direct.filter(|v| !v.is_empty()) {
+        return Some(v);
+    }
+    if let Some(path) = file {
+        match read_secret_file(path) {
+            Ok(v) if !v.is_empty() => return Some(v),
+            Ok(_) => eprintln!("Secret file {} is empty; ignoring it.", path),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+    if let Some(cmd) = command {
+        match read_secret_command(cmd) {
+            Ok(v) if !v.is_empty() => return Some(v),
+            Ok(_) => eprintln!("Secret command printed nothing; ignoring it: {}", cmd),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+    secrets.get(store_key)
+}
+
+fn resolve_discord_token(
+    direct: Option<String>,
+    cfg: Option<&crate::config::Config>,
+    secrets: &mut SecretResolver,
+) -> Option<String> {
+    resolve_credential(
+        direct,
+        cfg.and_then(|c| c.discord_token_file.as_deref()),
+        cfg.and_then(|c| c.discord_token_command.as_deref()),
+        "discord_token",
+        secrets,
+    )
+}
+
+fn run_secret_command(action: &SecretCommand) -> Result<()> {
+    use crate::secrets::{load_secrets, read_passphrase, save_secrets, store_exists, store_path};
+
+    let creating = !store_exists();
+    let passphrase = read_passphrase(creating && matches!(action, SecretCommand::Set { .. }))?;
+    let mut stored = load_secrets(&passphrase)?;
+    match action {
+        SecretCommand::Set { name, value } => {
+            let value = match value {
+                Some(v) => v.clone(),
+                None => dialoguer::Password::new()
+                    .with_prompt(format!("Value for {}", name.as_key()))
+                    .interact()
+                    .with_context(|| "Failed to read the secret value")?,
+            };
+            stored.insert(name.as_key().to_string(), value);
+            save_secrets(&passphrase, &stored)?;
+            println!("Stored {} in {}", name.as_key(), store_path().display());
+        }
+        SecretCommand::Get { name } => match stored.get(name.as_key()) {
+            Some(v) => println!("{}", v),
+            None => {
+                eprintln!("{} is not set in the secret store.", name.as_key());
+                std::process::exit(1);
+            }
+        },
+        SecretCommand::Remove { name } => {
+            if stored.remove(name.as_key()).is_some() {
+                save_secrets(&passphrase, &stored)?;
+                println!("Removed {} from the secret store.", name.as_key());
+            } else {
+                println!("{} was not set in the secret store.", name.as_key());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_discord_command(action: &DiscordCommand, token: Option<&str>, dry_run: bool, debug: bool) -> Result<()> {
+    if let DiscordCommand::History = action {
+        let entries = load_history()?;
+        if entries.is_empty() {
+            println!("No bio backups yet. They are recorded automatically before each --update-discord.");
+            return Ok(());
+        }
+        println!("Saved bio backups ({}):", crate::history::history_path().display());
+        for e in entries {
+            let when = e.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+            let first_line = e.bio.lines().next().unwrap_or("");
+            let owner = match (e.username.as_str(), e.account.as_str()) {
+                ("", "") => "unknown account".to_string(),
+                ("", id) => id.to_string(),
+                (name, _) => format!("@{}", name),
+            };
+            println!("  #{:<3} {}  {}  ({} chars)  {}", e.id, when, owner, bio_length(&e.bio), first_line);
+        }
+        return Ok(());
+    }
+
+    let Some(token) = token else {
+        eprintln!("Restoring a bio requires a Discord token. Use --discord-token, set DISCORD_TOKEN, run `topsongs secret set discord_token`, or set discord_token_file/discord_token_command in config.");
+        std::process::exit(2);
+    };
+    // Backups are keyed by the account they came from, so a token for another account never gets its bio.
+    let me = get_profile(token, debug)
+        .await
+        .with_context(|| "Failed to fetch current Discord bio")?;
+    let backup = match action {
+        DiscordCommand::Restore { id } => {
+            let Some(found) = find_backup(*id)? else {
+                eprintln!("No bio backup with id #{}. Run `topsongs discord history` to list them.", id);
+                std::process::exit(2);
+            };
+            if !found.account.is_empty() && found.account != me.id {
+                eprintln!(
+                    "Backup #{} belongs to another Discord account (@{}), not @{}. No update sent.",
+                    id, found.username, me.username
+                );
+                std::process::exit(2);
+            }
+            Some(found)
+        }
+        _ => latest_backup(&me.id)?,
+    };
+    let Some(backup) = backup else {
+        println!("No bio backups for @{} yet; nothing to undo.", me.username);
+        return Ok(());
+    };
+
+    if me.bio == backup.bio {
+        println!("Discord bio already matches backup #{}. No update sent.", backup.id);
+        return Ok(());
+    }
+    if dry_run {
+        println!("[Discord dry-run] Would restore backup #{}:\n{}", backup.id, backup.bio);
+        println!("[Discord dry-run] No changes were sent to Discord.");
+        return Ok(());
+    }
+    let saved = record_backup(&me.id, &me.username, &me.bio)
+        .with_context(|| "Failed to back up the current Discord bio, so no update was sent")?;
+    update_bio(token, &backup.bio, debug)
+        .await
+        .with_context(|| "Failed to update Discord bio")?;
+    println!("Restored backup #{}. The bio it replaced was saved as backup #{}.", backup.id, saved);
+    Ok(())
+}
+
+/// Show the pending bio change and ask whether to send it. A prompt failure exits with status 1.
+fn confirm_bio_change(
+    current_bio: &str,
+    new_bio: &str,
+    limit: usize,
+    notes: &[String],
+    applied: &[String],
+    machine_output: bool,
+) -> bool {
+    status!(machine_output, "\nChanges to your Discord bio:");
+    status!(machine_output, "{}", render_bio_diff(current_bio, new_bio).trim_end_matches('\n'));
+    status!(machine_output, "{}", length_summary(current_bio, new_bio, limit));
+    for note in notes {
+        status!(machine_output, "{}", note);
+    }
+    for note in applied {
+        status!(machine_output, "Auto-fit: {}", note);
+    }
+    match dialoguer::Confirm::new().with_prompt("Send this bio to Discord?").default(false).interact() {
+        Ok(answer) => answer,
+        Err(e) => {
+            eprintln!("Could not show the confirmation prompt ({}). Re-run with --yes to update without confirming.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Settings for the Discord profile fields other than the main bio.
+struct ProfileFields<'a> {
+    targets: &'a [DiscordTarget],
+    status_format: &'a str,
+    status_emoji: Option<&'a str>,
+    status_expires: Option<&'a str>,
+    pronouns_format: &'a str,
+    guild_ids: &'a [String],
+    /// Length budget and auto-fit strategies for the server bio
+    bio_limit: usize,
+    bio_fit: &'a [FitStrategy],
+    /// Report to stderr because stdout carries `--output-format` data
+    machine_output: bool,
+}
+
+/// Render a single-track field with the first selected track, shortening it to Discord's limit if needed.
+fn render_field(name: &str, tpl: &str, top: &Track, limit: usize) -> String {
+    let text = render_template(tpl, top).replace(['\r', '\n'], " ");
+    if text.chars().count() > limit {
+        eprintln!("Note: {} is longer than Discord's {} character limit; truncating.", name, limit);
+        truncate_with_ellipsis(&text, limit)
+    } else {
+        text
+    }
+}
+
+/// Ask before overwriting the status, pronouns or server bios, the same way the main bio is confirmed.
+fn confirm_field_changes(changes: &[String], machine_output: bool) -> bool {
+    status!(machine_output, "\nOther Discord profile fields to overwrite (their current values are not backed up):");
+    for change in changes {
+        status!(machine_output, "  {}", change);
+    }
+    match dialoguer::Confirm::new().with_prompt("Send these to Discord?").default(false).interact() {
+        Ok(answer) => answer,
+        Err(e) => {
+            eprintln!("Could not show the confirmation prompt ({}). Re-run with --yes to update without confirming.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Update the status, pronouns and guild bio targets. Failures are reported per field and do not stop the others.
+/// Unlike the main bio these fields are replaced wholesale and their previous values are not recorded in the
+/// bio history, so `discord undo` cannot bring them back; with `confirm` the new values are shown first.
+async fn update_profile_fields(
+    fields: &ProfileFields<'_>,
+    fit_input: &FitInput<'_>,
+    token: &str,
+    dry_run: bool,
+    confirm: bool,
+    debug: bool,
+) {
+    let Some(top) = fit_input.tracks.first() else { return };
+
+    let mut status = None;
+    if fields.targets.contains(&DiscordTarget::Status) {
+        let text = render_field("Custom status", fields.status_format, top, STATUS_TEXT_LIMIT);
+        match fields.status_expires.map(parse_status_expiry).transpose() {
+            Ok(expires_at) => status = Some((text, expires_at)),
+            Err(e) => eprintln!("{}. Custom status not updated.", e),
+        }
+    }
+    let status_line = |text: &str, expires_at: Option<chrono::DateTime<chrono::Utc>>| {
+        let emoji = fields.status_emoji.map(|e| format!("{} ", e)).unwrap_or_default();
+        let expiry = expires_at
+            .map(|t| format!(" (expires {})", t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default();
+        format!("{}{}{}", emoji, text, expiry)
+    };
+
+    let pronouns = fields
+        .targets
+        .contains(&DiscordTarget::Pronouns)
+        .then(|| render_field("Pronouns", fields.pronouns_format, top, PRONOUNS_LIMIT));
+
+    let mut guild_bio = None;
+    if fields.targets.contains(&DiscordTarget::GuildBio) {
+        if fields.guild_ids.is_empty() {
+            eprintln!("The guild-bio target needs at least one --guild-id (or discord_guild_ids in config).");
+        } else if let Some(bad) = fields.guild_ids.iter().find(|id| !is_snowflake(id)) {
+            eprintln!("Invalid guild id '{}': expected the numeric server id. No server bios updated.", bad);
+        } else {
+            // A server bio is replaced wholesale, so the budget only covers the rendered list.
+            let fitted = fit_to_limit(fit_input, fields.bio_limit, fields.bio_fit, bio_length);
+            for warning in &fitted.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            for note in &fitted.applied {
+                status!(fields.machine_output, "Server bio auto-fit: {}", note);
+            }
+            if fitted.fits {
+                guild_bio = Some(fitted.output);
+            } else {
+                eprintln!(
+                    "Server bio would be {} characters, over the limit of {}. No server bios updated. Use --bio-fit to shrink it automatically.",
+                    fitted.length, fields.bio_limit
+                );
+            }
+        }
+    }
+
+    if dry_run {
+        if let Some((text, expires_at)) = &status {
+            status!(fields.machine_output, "[Discord dry-run] Would set custom status to: {}", status_line(text, *expires_at));
+        }
+        if let Some(pronouns) = &pronouns {
+            status!(fields.machine_output, "[Discord dry-run] Would set pronouns to: {}", pronouns);
+        }
+        if let Some(bio) = &guild_bio {
+            for guild_id in fields.guild_ids {
+                status!(fields.machine_output, "[Discord dry-run] Would set server bio in guild {} to:\n{}", guild_id, bio);
+            }
+        }
+        return;
+    }
+
+    if confirm {
+        let mut changes = Vec::new();
+        if let Some((text, expires_at)) = &status {
+            changes.push(format!("Custom status: {}", status_line(text, *expires_at)));
+        }
+        if let Some(pronouns) = &pronouns {
+            changes.push(format!("Pronouns: {}", pronouns));
+        }
+        if let Some(bio) = &guild_bio {
+            changes.push(format!("Server bio in guild(s) {}:\n{}", fields.guild_ids.join(", "), bio));
+        }
+        if !changes.is_empty() && !confirm_field_changes(&changes, fields.machine_output) {
+            status!(fields.machine_output, "Other profile fields left unchanged.");
+            return;
+        }
+    }
+
+    if let Some((text, expires_at)) = &status {
+        match update_custom_status(token, text, fields.status_emoji, *expires_at, debug).await {
+            Ok(()) => status!(fields.machine_output, "Custom status set to: {}", status_line(text, *expires_at)),
+            Err(e) => eprintln!("Failed to update custom status: {}", e),
+        }
+    }
+    if let Some(pronouns) = &pronouns {
+        match update_pronouns(token, pronouns, debug).await {
+            Ok(()) => status!(fields.machine_output, "Pronouns set to: {}", pronouns),
+            Err(e) => eprintln!("Failed to update pronouns: {}", e),
+        }
+    }
+    if let Some(bio) = &guild_bio {
+        for guild_id in fields.guild_ids {
+            match update_guild_bio(token, guild_id, bio, debug).await {
+                Ok(()) => status!(fields.machine_output, "Server bio updated in guild {}.", guild_id),
+                Err(e) => eprintln!("Failed to update server bio in guild {}: {}", guild_id, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::lastfm::Track;
+
+/// Comparison key for "is this the same song": lowercased, punctuation dropped, whitespace collapsed.
+/// "Don't Stop Me Now" and "Dont stop me now!" share a key.
+pub fn normalize_key(s: &str) -> String {
+    let cleaned: String = s
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else if c.is_whitespace() { ' ' } else { '\0' })
+        .filter(|c| *c != '\0')
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Comparison key for a title. The built-in remaster, edit/mix and edition tags are always ignored, even when
+/// the display cleanup keeps them, so "Song" and "Song - 2011 Remaster" merge by default.
+pub fn title_key(title: &str) -> String {
+    normalize_key(&crate::text::strip_version_tags(title))
+}
+
+/// Fold tracks that share a key (`keys[i]` belongs to `tracks[i]`) into the first, highest-ranked entry:
+/// playcounts are summed and `merged` counts the folded entries. If anything was merged the result is
+/// re-sorted by playcount, keeping Last.fm's order for ties.
+pub fn merge_duplicates(tracks: Vec<Track>, keys: &[String]) -> Vec<Track> {
+    let mut merged: Vec<(String, Track, u64)> = Vec::with_capacity(tracks.len());
+    for (track, key) in tracks.into_iter().zip(keys) {
+        let plays = track.playcount.trim().parse::<u64>().unwrap_or(0);
+        match merged.iter_mut().find(|(k, _, _)| k == key) {
+            Some((_, first, total)) => {
+                *total += plays;
+                first.merged += 1;
+            }
+            None => merged.push((key.clone(), track, plays)),
+        }
+    }
+    if merged.iter().any(|(_, t, _)| t.merged > 0) {
+        merged.sort_by_key(|e| std::cmp::Reverse(e.2));
+    }
+    merged
+        .into_iter()
+        .map(|(_, mut track, total)| {
+            if track.merged > 0 {
+                track.playcount = total.to_string();
+            }
+            track
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lastfm::Artist;
+
+    fn track(name: &str, plays: &str) -> Track {
+        Track {
+            name: name.to_string(),
+            playcount: plays.to_string(),
+            artist: Artist { name: "Queen".to_string() },
+            ..Default::default()
+        }
+    }
+
+    fn names(tracks: &[Track]) -> Vec<(&str, &str, u32)> {
+        tracks.iter().map(|t| (t.name.as_str(), t.playcount.as_str(), t.merged)).collect()
+    }
+
+    #[test]
+    fn keys_ignore_case_punctuation_and_spacing() {
+        assert_eq!(normalize_key("Don't Stop Me Now"), normalize_key("Dont  stop me now!"));
+        assert_eq!(normalize_key("  Jóga\t"), "jóga");
+        assert_ne!(normalize_key("Song 2"), normalize_key("Song"));
+    }
+
+    #[test]
+    fn title_keys_ignore_version_tags_but_not_live_or_remixes() {
+        let key = title_key("Bohemian Rhapsody");
+        assert_eq!(title_key("Bohemian Rhapsody - Remastered 2011"), key);
+        assert_eq!(title_key("Bohemian Rhapsody (2011 Remaster)"), key);
+        assert_eq!(title_key("Bohemian Rhapsody - Radio Edit"), key);
+        assert_eq!(title_key("Bohemian Rhapsody (Deluxe Edition)"), key);
+        assert_ne!(title_key("Bohemian Rhapsody (Live at Wembley)"), key);
+        assert_ne!(title_key("Bohemian Rhapsody - Skrillex Remix"), key);
+    }
+
+    #[test]
+    fn merging_sums_playcounts_and_reranks() {
+        let tracks = vec![track("A", "50"), track("B", "40"), track("A - Remastered", "30"), track("C", "10")];
+        let keys: Vec<String> = tracks.iter().map(|t| title_key(&t.name)).collect();
+        // B has 40 plays, but A now has 80 and stays first; the first entry's title is kept
+        assert_eq!(names(&merge_duplicates(tracks, &keys)), [("A", "80", 1), ("B", "40", 0), ("C", "10", 0)]);
+
+        let tracks = vec![track("B", "40"), track("A", "30"), track("C", "20"), track("A (Remastered)", "15")];
+        let keys: Vec<String> = tracks.iter().map(|t| title_key(&t.name)).collect();
+        assert_eq!(names(&merge_duplicates(tracks, &keys)), [("A", "45", 1), ("B", "40", 0), ("C", "20", 0)]);
+    }
+
+    #[test]
+    fn ties_keep_lastfm_order() {
+        let tracks = vec![track("X", "30"), track("Y", "20"), track("Z", "20"), track("X", "5"), track("W", "25")];
+        let keys: Vec<String> = tracks.iter().map(|t| title_key(&t.name)).collect();
+        assert_eq!(names(&merge_duplicates(tracks, &keys)), [("X", "35", 1), ("W", "25", 0), ("Y", "20", 0), ("Z", "20", 0)]);
+    }
+
+    #[test]
+    fn nothing_merged_keeps_the_list_untouched() {
+        // Last.fm order is kept as is, even where it disagrees with the playcounts
+        let tracks = vec![track("A", "5"), track("B", "9")];
+        let keys: Vec<String> = tracks.iter().map(|t| title_key(&t.name)).collect();
+        assert_eq!(names(&merge_duplicates(tracks, &keys)), [("A", "5", 0), ("B", "9", 0)]);
+    }
+}
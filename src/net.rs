@@ -27,43 +27,56 @@ fn redact_url(url: &str) -> String {
             out.replace_range(start..end, "<redacted>");
         }
     }
+    // Discord webhook URLs carry their token in the path: /api/webhooks/<id>/<token>
+    if let Some(idx) = out.find("/webhooks/") {
+        let id_start = idx + "/webhooks/".len();
+        if let Some(slash) = out[id_start..].find('/') {
+            let start = id_start + slash + 1;
+            let end = out[start..]
+                .find(['?', '/'])
+                .map(|i| start + i)
+                .unwrap_or(out.len());
+            out.replace_range(start..end, "<redacted>");
+        }
+    }
     out
 }
 
 pub async fn send_with_debug(rb: reqwest::RequestBuilder, debug: bool, body_preview: Option<String>) -> Result<reqwest::Response> {
-    if debug {
-        if let Some(cloned) = rb.try_clone() {
-            match cloned.build() {
-                Ok(req) => {
-                    let line = format!("{} {}", req.method(), redact_url(req.url().as_str()));
-                    eprintln!("{}", dim(&format!("→ Request: {}", line)));
-                    // headers
-                    for (name, value) in req.headers().iter() {
-                        let val = value.to_str().unwrap_or("<non-utf8>");
-                        let red = redact_header(name.as_str(), val);
-                        eprintln!("{}", dim(&format!("  {}: {}", name, red)));
-                    }
-                    if let Some(b) = &body_preview {
-                        if !b.trim().is_empty() {
-                            eprintln!("{}", dim("  (body):"));
-                            for line in b.lines() {
-                                eprintln!("{}", dim(&format!("    {}", line)));
-                            }
-                        }
-                    }
+    if debug
+        && let Some(cloned) = rb.try_clone()
+    {
+        match cloned.build() {
+            Ok(req) => {
+                let line = format!("{} {}", req.method(), redact_url(req.url().as_str()));
+                eprintln!("{}", dim(&format!("→ Request: {}", line)));
+                // headers
+                for (name, value) in req.headers().iter() {
+                    let val = value.to_str().unwrap_or("<non-utf8>");
+                    let red = redact_header(name.as_str(), val);
+                    eprintln!("{}", dim(&format!("  {}: {}", name, red)));
                 }
-                Err(e) => {
-                    eprintln!("{}", dim(&format!("(failed to build request for debug: {})", e)));
+                if let Some(b) = &body_preview
+                    && !b.trim().is_empty()
+                {
+                    eprintln!("{}", dim("  (body):"));
+                    for line in b.lines() {
+                        eprintln!("{}", dim(&format!("    {}", line)));
+                    }
                 }
             }
+            Err(e) => {
+                eprintln!("{}", dim(&format!("(failed to build request for debug: {})", e)));
+            }
         }
     }
 
-    let resp_res = rb.send().await;
-    if let Err(e) = &resp_res {
-        if debug {
-            eprintln!("HTTP request send error: {}", e);
-        }
+    // reqwest errors quote the full URL, which carries the token for webhooks and the key for Last.fm.
+    let resp_res = rb.send().await.map_err(|e| e.without_url());
+    if let Err(e) = &resp_res
+        && debug
+    {
+        eprintln!("HTTP request send error: {}", e);
     }
     let resp = resp_res?;
 
@@ -76,7 +89,7 @@ pub async fn send_with_debug(rb: reqwest::RequestBuilder, debug: bool, body_prev
         let body = resp
             .text()
             .await
-            .unwrap_or_else(|e| format!("<failed to read error body: {}>", e));
+            .unwrap_or_else(|e| format!("<failed to read error body: {}>", e.without_url()));
         if debug {
             eprintln!("HTTP error status: {}\nResponse body: {}", status, body);
         }
@@ -0,0 +1,271 @@
+use serde_json::{json, Value};
+
+use crate::cli::OutputFormat;
+use crate::lastfm::Track;
+use crate::text::{display_width, pad_to_width, truncate_to_width, Align};
+
+/// Context printed alongside the tracks by the machine-readable formats.
+pub struct ListMeta<'a> {
+    pub username: &'a str,
+    pub period: &'a str,
+    /// The final rendered list; only set after selection (not in `--query` mode)
+    pub rendered: Option<&'a str>,
+}
+
+const COLUMNS: [&str; 7] = ["rank", "artist", "track", "playcount", "url", "featured", "artists"];
+
+fn row(rank: usize, t: &Track) -> [String; 7] {
+    [
+        rank.to_string(),
+        t.artist.name.clone(),
+        t.name.clone(),
+        t.playcount.clone(),
+        t.url.clone(),
+        t.featured.clone(),
+        t.all_artists(),
+    ]
+}
+
+/// Format `tracks` for `format`. `Text` is handled by the caller's human-readable listing and yields `None`.
+pub fn format_tracks(tracks: &[Track], format: OutputFormat, meta: &ListMeta) -> Option<String> {
+    match format {
+        OutputFormat::Text => None,
+        OutputFormat::Json => Some(to_json(tracks, meta)),
+        OutputFormat::Csv => Some(to_csv(tracks)),
+        OutputFormat::Markdown => Some(to_markdown(tracks)),
+        OutputFormat::Html => Some(to_html(tracks)),
+    }
+}
+
+fn to_json(tracks: &[Track], meta: &ListMeta) -> String {
+    let items: Vec<Value> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            json!({
+                "rank": i + 1,
+                "artist": t.artist.name,
+                "track": t.name,
+                "playcount": t.playcount.trim().parse::<u64>().ok(),
+                "url": t.url,
+                "featured": t.featured,
+                "artists": t.all_artists(),
+                "merged": t.merged,
+            })
+        })
+        .collect();
+    let mut doc = json!({
+        "username": meta.username,
+        "period": meta.period,
+        "tracks": items,
+    });
+    if let Some(rendered) = meta.rendered {
+        doc["rendered"] = Value::String(rendered.to_string());
+    }
+    let mut out = serde_json::to_string_pretty(&doc).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn to_csv(tracks: &[Track]) -> String {
+    let mut out = COLUMNS.join(",");
+    out.push('\n');
+    for (i, t) in tracks.iter().enumerate() {
+        let fields: Vec<String> = row(i + 1, t).iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Escape a table cell: pipes would end the cell and brackets would break the `[title](url)` link.
+fn markdown_cell(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(['\r', '\n'], " ")
+}
+
+fn to_markdown(tracks: &[Track]) -> String {
+    let mut out = String::from("| # | Artist | Track | Plays |\n|---:|---|---|---:|\n");
+    for (i, t) in tracks.iter().enumerate() {
+        let track = if t.url.is_empty() {
+            markdown_cell(&t.name)
+        } else {
+            format!("[{}]({})", markdown_cell(&t.name), t.url.replace(')', "%29"))
+        };
+        out.push_str(&format!("| {} | {} | {} | {} |\n", i + 1, markdown_cell(&t.artist.name), track, markdown_cell(&t.playcount)));
+    }
+    out
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn to_html(tracks: &[Track]) -> String {
+    let mut out = String::from("<table>\n  <thead>\n    <tr><th>#</th><th>Artist</th><th>Track</th><th>Plays</th></tr>\n  </thead>\n  <tbody>\n");
+    for (i, t) in tracks.iter().enumerate() {
+        let track = if t.url.is_empty() {
+            html_escape(&t.name)
+        } else {
+            format!("<a href=\"{}\">{}</a>", html_escape(&t.url), html_escape(&t.name))
+        };
+        out.push_str(&format!(
+            "    <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            i + 1,
+            html_escape(&t.artist.name),
+            track,
+            html_escape(&t.playcount)
+        ));
+    }
+    out.push_str("  </tbody>\n</table>\n");
+    out
+}
+
+/// Widest the artist/track columns of the terminal table may get before names are truncated.
+const TABLE_ARTIST_MAX: usize = 28;
+const TABLE_TRACK_MAX: usize = 44;
+
+/// Terminal listing with aligned columns, measured in display width so CJK and emoji line up.
+pub fn aligned_table(tracks: &[Track]) -> String {
+    let rows: Vec<[String; 4]> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            [
+                format!("{}.", i + 1),
+                truncate_to_width(&t.artist.name, TABLE_ARTIST_MAX),
+                truncate_to_width(&t.name, TABLE_TRACK_MAX),
+                t.playcount.trim().parse::<u64>().unwrap_or(0).to_string(),
+            ]
+        })
+        .collect();
+    let header = ["#", "Artist", "Track", "Plays"];
+    let align = [Align::Right, Align::Left, Align::Left, Align::Right];
+    let mut widths = header.map(display_width);
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(display_width(cell));
+        }
+    }
+
+    let line = |cells: [&str; 4]| -> String {
+        let padded: Vec<String> = (0..4).map(|i| pad_to_width(cells[i], widths[i], align[i])).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut out = line(header);
+    out.push('\n');
+    out.push_str(&widths.iter().map(|w| "─".repeat(*w)).collect::<Vec<_>>().join("  "));
+    for row in &rows {
+        out.push('\n');
+        out.push_str(&line([&row[0], &row[1], &row[2], &row[3]]));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lastfm::Artist;
+
+    fn track(name: &str, artist: &str, plays: &str, url: &str) -> Track {
+        Track {
+            name: name.to_string(),
+            playcount: plays.to_string(),
+            artist: Artist { name: artist.to_string() },
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn meta() -> ListMeta<'static> {
+        ListMeta { username: "alice", period: "7day", rendered: None }
+    }
+
+    fn format(tracks: &[Track], format: OutputFormat) -> String {
+        format_tracks(tracks, format, &meta()).unwrap()
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_newlines() {
+        let tracks = [track("Hello, \"World\"", "A\nB", "7", "")];
+        let out = format(&tracks, OutputFormat::Csv);
+        let mut lines = out.splitn(2, '\n');
+        assert_eq!(lines.next(), Some(COLUMNS.join(",").as_str()));
+        assert!(lines.next().unwrap().starts_with("1,\"A\nB\",\"Hello, \"\"World\"\"\",7,"));
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a\rb"), "\"a\rb\"");
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_and_brackets() {
+        let tracks = [track("Live [Remastered] | B-side", "AC|DC", "5", "https://last.fm/x_(y)")];
+        let out = format(&tracks, OutputFormat::Markdown);
+        assert_eq!(
+            out.lines().nth(2),
+            Some("| 1 | AC\\|DC | [Live \\[Remastered\\] \\| B-side](https://last.fm/x_(y%29) | 5 |")
+        );
+    }
+
+    #[test]
+    fn html_escapes_entities() {
+        let tracks = [track("<b>Rock & Roll</b>", "Guns N' Roses", "5", "https://x.test/?a=1&b=\"2\"")];
+        let out = format(&tracks, OutputFormat::Html);
+        assert!(out.contains("<td>Guns N&#39; Roses</td>"), "{}", out);
+        assert!(out.contains("<a href=\"https://x.test/?a=1&amp;b=&quot;2&quot;\">&lt;b&gt;Rock &amp; Roll&lt;/b&gt;</a>"), "{}", out);
+    }
+
+    #[test]
+    fn json_shape() {
+        let tracks = [track("Heroes", "David Bowie", "12", "https://x.test"), track("Jóga", "Björk", "n/a", "")];
+        let doc: Value = serde_json::from_str(&format(&tracks, OutputFormat::Json)).unwrap();
+        assert_eq!(doc["username"], "alice");
+        assert_eq!(doc["period"], "7day");
+        assert!(doc.get("rendered").is_none());
+        assert_eq!(doc["tracks"][0]["rank"], 1);
+        assert_eq!(doc["tracks"][0]["playcount"], 12);
+        assert_eq!(doc["tracks"][0]["url"], "https://x.test");
+        // A play count that is not a number becomes null rather than a string
+        assert_eq!(doc["tracks"][1]["playcount"], Value::Null);
+
+        let rendered = ListMeta { rendered: Some("- Heroes"), ..meta() };
+        let doc: Value = serde_json::from_str(&format_tracks(&tracks, OutputFormat::Json, &rendered).unwrap()).unwrap();
+        assert_eq!(doc["rendered"], "- Heroes");
+        assert_eq!(format_tracks(&tracks, OutputFormat::Text, &meta()), None);
+    }
+
+    #[test]
+    fn featured_artists_are_listed() {
+        let mut t = track("Get Lucky", "Daft Punk", "9", "");
+        t.featured = "Pharrell Williams, Nile Rodgers".to_string();
+        t.artists = "Daft Punk, Pharrell Williams, Nile Rodgers".to_string();
+        let plain = track("Heroes", "David Bowie", "1", "");
+        let tracks = [t, plain];
+        let doc: Value = serde_json::from_str(&format(&tracks, OutputFormat::Json)).unwrap();
+        assert_eq!(doc["tracks"][0]["featured"], "Pharrell Williams, Nile Rodgers");
+        assert_eq!(doc["tracks"][0]["artists"], "Daft Punk, Pharrell Williams, Nile Rodgers");
+        // Without extracted credits the artists are just the main artist
+        assert_eq!(doc["tracks"][1]["artists"], "David Bowie");
+        assert_eq!(doc["tracks"][1]["featured"], "");
+
+        let csv = format(&tracks, OutputFormat::Csv);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "rank,artist,track,playcount,url,featured,artists");
+        assert_eq!(rows[1], "1,Daft Punk,Get Lucky,9,,\"Pharrell Williams, Nile Rodgers\",\"Daft Punk, Pharrell Williams, Nile Rodgers\"");
+        assert_eq!(rows[2], "2,David Bowie,Heroes,1,,,David Bowie");
+    }
+}
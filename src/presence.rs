@@ -0,0 +1,300 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Discord IPC frame opcodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Handshake = 0,
+    Frame = 1,
+    Close = 2,
+    Ping = 3,
+    Pong = 4,
+}
+
+impl Opcode {
+    fn from_u32(v: u32) -> Option<Opcode> {
+        match v {
+            0 => Some(Opcode::Handshake),
+            1 => Some(Opcode::Frame),
+            2 => Some(Opcode::Close),
+            3 => Some(Opcode::Ping),
+            4 => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+}
+
+/// Activity type shown as "Listening to ..." in the Discord client.
+const ACTIVITY_LISTENING: u8 = 2;
+/// Discord rejects activity strings longer than this.
+const ACTIVITY_TEXT_LIMIT: usize = 128;
+/// Discord rejects activity strings shorter than this.
+const ACTIVITY_TEXT_MIN: usize = 2;
+/// Frames larger than this are treated as a protocol error rather than allocated.
+const MAX_FRAME_LEN: u32 = 64 * 1024;
+
+/// Encode one IPC frame: little-endian opcode and payload length, followed by the JSON payload.
+pub fn encode_frame(op: Opcode, payload: &Value) -> Vec<u8> {
+    let body = payload.to_string().into_bytes();
+    let mut out = Vec::with_capacity(8 + body.len());
+    out.extend_from_slice(&(op as u32).to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<(Opcode, Value)> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header).await.context("Discord IPC connection closed")?;
+    let op_raw = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let op = Opcode::from_u32(op_raw).ok_or_else(|| anyhow!("Unknown Discord IPC opcode {}", op_raw))?;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!("Discord IPC frame too large ({} bytes)", len));
+    }
+    let mut body = vec![0u8; len as usize];
+    reader.read_exact(&mut body).await.context("Discord IPC connection closed mid-frame")?;
+    let value = serde_json::from_slice(&body).context("Discord IPC sent invalid JSON")?;
+    Ok((op, value))
+}
+
+pub struct IpcClient<S> {
+    stream: S,
+    nonce: u64,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> IpcClient<S> {
+    /// Perform the handshake on an already connected stream and wait for Discord's READY event.
+    pub async fn handshake(stream: S, client_id: &str) -> Result<Self> {
+        let mut client = IpcClient { stream, nonce: 0 };
+        client.send(Opcode::Handshake, &json!({ "v": 1, "client_id": client_id })).await?;
+        let reply = client.recv().await?;
+        if reply.get("evt").and_then(Value::as_str) != Some("READY") {
+            return Err(anyhow!("Discord IPC handshake failed: {}", describe_error(&reply)));
+        }
+        Ok(client)
+    }
+
+    async fn send(&mut self, op: Opcode, payload: &Value) -> Result<()> {
+        self.stream.write_all(&encode_frame(op, payload)).await.context("Failed to write to Discord IPC")?;
+        self.stream.flush().await.context("Failed to write to Discord IPC")?;
+        Ok(())
+    }
+
+    /// Read the next data frame, answering pings along the way.
+    async fn recv(&mut self) -> Result<Value> {
+        loop {
+            let (op, value) = read_frame(&mut self.stream).await?;
+            match op {
+                Opcode::Frame => return Ok(value),
+                Opcode::Ping => self.send(Opcode::Pong, &value).await?,
+                Opcode::Close => return Err(anyhow!("Discord closed the IPC connection: {}", describe_error(&value))),
+                Opcode::Handshake | Opcode::Pong => {}
+            }
+        }
+    }
+
+    async fn command(&mut self, args: Value) -> Result<Value> {
+        self.nonce += 1;
+        let nonce = format!("topsongs-{}-{}", std::process::id(), self.nonce);
+        self.send(Opcode::Frame, &json!({ "cmd": "SET_ACTIVITY", "args": args, "nonce": nonce })).await?;
+        // Events (DISPATCH frames) can arrive before the answer; only the frame echoing our nonce is the reply.
+        let reply = loop {
+            let frame = self.recv().await?;
+            if frame.get("nonce").and_then(Value::as_str) == Some(nonce.as_str()) {
+                break frame;
+            }
+        };
+        if reply.get("evt").and_then(Value::as_str) == Some("ERROR") {
+            return Err(anyhow!("Discord rejected the activity: {}", describe_error(&reply)));
+        }
+        Ok(reply)
+    }
+
+    pub async fn set_activity(&mut self, details: &str, state: &str) -> Result<()> {
+        let mut activity = json!({ "type": ACTIVITY_LISTENING });
+        if let Some(details) = activity_text(details) {
+            activity["details"] = json!(details);
+        }
+        if let Some(state) = activity_text(state) {
+            activity["state"] = json!(state);
+        }
+        self.command(json!({ "pid": std::process::id(), "activity": activity })).await?;
+        Ok(())
+    }
+
+    pub async fn clear_activity(&mut self) -> Result<()> {
+        self.command(json!({ "pid": std::process::id() })).await?;
+        Ok(())
+    }
+}
+
+/// `s` trimmed and cut to Discord's limit, or `None` when it is too short to be accepted and must be left out.
+pub fn activity_text(s: &str) -> Option<String> {
+    let s = s.trim();
+    (s.chars().count() >= ACTIVITY_TEXT_MIN).then(|| clamp(s))
+}
+
+fn clamp(s: &str) -> String {
+    if s.chars().count() > ACTIVITY_TEXT_LIMIT {
+        crate::fit::truncate_with_ellipsis(s, ACTIVITY_TEXT_LIMIT)
+    } else {
+        s.to_string()
+    }
+}
+
+fn describe_error(value: &Value) -> String {
+    value
+        .pointer("/data/message")
+        .or_else(|| value.get("message"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(unix)]
+pub type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+pub type IpcStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// Candidate IPC endpoints, in the order Discord's own SDK tries them.
+#[cfg(unix)]
+pub fn ipc_socket_candidates() -> Vec<std::path::PathBuf> {
+    use std::path::PathBuf;
+    let mut bases: Vec<PathBuf> = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .filter_map(|k| std::env::var_os(k).map(PathBuf::from))
+        .collect();
+    bases.push(PathBuf::from("/tmp"));
+    let mut out = Vec::new();
+    for base in bases {
+        // Plain install first, then the Flatpak and Snap sandboxes
+        for sub in ["", "app/com.discordapp.Discord", "snap.discord"] {
+            for i in 0..10 {
+                out.push(base.join(sub).join(format!("discord-ipc-{}", i)));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(unix)]
+async fn open_stream() -> Result<IpcStream> {
+    for path in ipc_socket_candidates() {
+        if let Ok(stream) = tokio::net::UnixStream::connect(&path).await {
+            return Ok(stream);
+        }
+    }
+    Err(anyhow!("Could not find a running Discord client (no discord-ipc socket in $XDG_RUNTIME_DIR or /tmp)"))
+}
+
+#[cfg(windows)]
+async fn open_stream() -> Result<IpcStream> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+    for i in 0..10 {
+        if let Ok(pipe) = ClientOptions::new().open(format!(r"\\.\pipe\discord-ipc-{}", i)) {
+            return Ok(pipe);
+        }
+    }
+    Err(anyhow!("Could not find a running Discord client (no discord-ipc named pipe)"))
+}
+
+/// Connect to the local Discord client and complete the handshake for the given application id.
+pub async fn connect(client_id: &str) -> Result<IpcClient<IpcStream>> {
+    let stream = open_stream().await?;
+    IpcClient::handshake(stream, client_id).await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    fn socket_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("topsongs-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn encodes_header_little_endian() {
+        let frame = encode_frame(Opcode::Frame, &json!({ "a": 1 }));
+        assert_eq!(&frame[..4], &1u32.to_le_bytes());
+        assert_eq!(&frame[4..8], &7u32.to_le_bytes());
+        assert_eq!(&frame[8..], br#"{"a":1}"#);
+    }
+
+    #[tokio::test]
+    async fn reads_back_encoded_frame() {
+        let bytes = encode_frame(Opcode::Ping, &json!({ "x": "y" }));
+        let (op, value) = read_frame(&mut bytes.as_slice()).await.unwrap();
+        assert_eq!(op, Opcode::Ping);
+        assert_eq!(value, json!({ "x": "y" }));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_opcode_and_oversized_frames() {
+        let mut bad_op = 9u32.to_le_bytes().to_vec();
+        bad_op.extend_from_slice(&0u32.to_le_bytes());
+        assert!(read_frame(&mut bad_op.as_slice()).await.is_err());
+
+        let mut huge = 1u32.to_le_bytes().to_vec();
+        huge.extend_from_slice(&(MAX_FRAME_LEN + 1).to_le_bytes());
+        assert!(read_frame(&mut huge.as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn handshake_and_set_activity_against_fake_server() {
+        let path = socket_path("ok");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let (op, hello) = read_frame(&mut sock).await.unwrap();
+            assert_eq!(op, Opcode::Handshake);
+            assert_eq!(hello, json!({ "v": 1, "client_id": "123" }));
+            sock.write_all(&encode_frame(Opcode::Frame, &json!({ "cmd": "DISPATCH", "evt": "READY" }))).await.unwrap();
+
+            let (op, cmd) = read_frame(&mut sock).await.unwrap();
+            assert_eq!(op, Opcode::Frame);
+            // A ping in between must be answered with a pong carrying the same payload
+            sock.write_all(&encode_frame(Opcode::Ping, &json!({ "n": 1 }))).await.unwrap();
+            let (op, pong) = read_frame(&mut sock).await.unwrap();
+            assert_eq!(op, Opcode::Pong);
+            assert_eq!(pong, json!({ "n": 1 }));
+            // An unrelated event arriving first must not be taken for the reply
+            let event = json!({ "cmd": "DISPATCH", "evt": "ERROR", "nonce": null, "data": { "message": "unrelated" } });
+            sock.write_all(&encode_frame(Opcode::Frame, &event)).await.unwrap();
+            sock.write_all(&encode_frame(Opcode::Frame, &json!({ "cmd": "SET_ACTIVITY", "nonce": cmd["nonce"] }))).await.unwrap();
+            cmd
+        });
+
+        let stream = IpcStream::connect(&path).await.unwrap();
+        let mut client = IpcClient::handshake(stream, "123").await.unwrap();
+        client.set_activity("One More Time", " ").await.unwrap();
+
+        let cmd = server.await.unwrap();
+        assert_eq!(cmd["cmd"], "SET_ACTIVITY");
+        assert_eq!(cmd["args"]["activity"]["details"], "One More Time");
+        // Discord rejects strings under two characters, so a blank state is left out
+        assert!(cmd["args"]["activity"].get("state").is_none());
+        assert_eq!(cmd["args"]["activity"]["type"], ACTIVITY_LISTENING);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn surfaces_handshake_rejection() {
+        let path = socket_path("reject");
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let _ = read_frame(&mut sock).await.unwrap();
+            sock.write_all(&encode_frame(Opcode::Close, &json!({ "code": 4000, "message": "Invalid Client ID" }))).await.unwrap();
+        });
+
+        let stream = IpcStream::connect(&path).await.unwrap();
+        let err = IpcClient::handshake(stream, "bad").await.err().expect("handshake should fail");
+        assert!(err.to_string().contains("Invalid Client ID"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,9 +1,90 @@
 ﻿use crate::lastfm::Track;
+use crate::template;
 
+/// Look up a per-track variable: `{rank}` (1-based position), `{artist}`, `{track}`, `{playcount}`, `{url}`,
+/// `{featured}` (artists credited in the title) and `{artists}` (main + featured artists).
+fn track_var(track: &Track, rank: usize, name: &str) -> Option<String> {
+    match name {
+        "rank" => Some(rank.to_string()),
+        "artist" => Some(track.artist.name.clone()),
+        "track" => Some(track.name.clone()),
+        "playcount" => Some(track.playcount.clone()),
+        "url" => Some(track.url.clone()),
+        "featured" => Some(track.featured.clone()),
+        "artists" => Some(track.all_artists()),
+        _ => None,
+    }
+}
+
+/// Render `tpl` for a single track that sits at position `rank` (1-based) in the list.
+pub fn render_ranked(tpl: &str, track: &Track, rank: usize) -> String {
+    template::render(tpl, &|name| track_var(track, rank, name))
+}
+
+/// Render `tpl` for a single track shown on its own (e.g. the status line), so `{rank}` is 1.
 pub fn render_template(tpl: &str, track: &Track) -> String {
-    tpl.replace("{artist}", &track.artist.name)
-        .replace("{track}", &track.name)
-        .replace("{playcount}", &track.playcount)
+    render_ranked(tpl, track, 1)
+}
+
+/// Render every track with `tpl` and wrap the joined result in prefix/suffix.
+/// `join`, `prefix` and `suffix` are expected to have escapes interpreted already; they are templates
+/// too and can use `{count}` (number of entries). With `markdown`, token values are escaped for Discord.
+pub fn render_list(tracks: &[Track], tpl: &str, join: &str, prefix: &str, suffix: &str, markdown: bool) -> String {
+    let nodes = template::parse(tpl);
+    let rendered: Vec<String> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| template::render_nodes(&nodes, &|name| track_var(t, i + 1, name), markdown))
+        .collect();
+    let count = tracks.len().to_string();
+    let list_var = |name: &str| (name == "count").then(|| count.clone());
+    format!(
+        "{}{}{}",
+        template::render(prefix, &list_var),
+        rendered.join(&template::render(join, &list_var)),
+        template::render(suffix, &list_var)
+    )
+}
+
+/// List-level values for whole-list templates (`--list-template`).
+pub struct ListContext<'a> {
+    pub username: &'a str,
+    pub period: &'a str,
+    /// Local date the list was rendered, `YYYY-MM-DD`
+    pub date: String,
+}
+
+/// Render a whole-list template. Outside the `{#for}` loop it can use `{count}`, `{total_plays}`,
+/// `{period}`, `{username}` and `{date}`; inside the loop every per-track token plus `{entry}`
+/// (the track rendered with `entry_format`) and `loop.index` / `loop.first` / `loop.last`.
+pub fn render_list_template(
+    list_tpl: &str,
+    tracks: &[Track],
+    entry_format: &str,
+    ctx: &ListContext,
+    markdown: bool,
+) -> String {
+    let entry_nodes = template::parse(entry_format);
+    let count = tracks.len().to_string();
+    let total_plays: u64 = tracks.iter().filter_map(|t| t.playcount.trim().parse::<u64>().ok()).sum();
+    let total_plays = total_plays.to_string();
+    let lookup = |name: &str| match name {
+        "count" => Some(count.clone()),
+        "total_plays" => Some(total_plays.clone()),
+        "period" => Some(ctx.period.to_string()),
+        "username" => Some(ctx.username.to_string()),
+        "date" => Some(ctx.date.clone()),
+        _ => None,
+    };
+    let item = |idx: usize, name: &str| {
+        let track = &tracks[idx];
+        if name == "entry" {
+            return Some(template::render_nodes(&entry_nodes, &|n| track_var(track, idx + 1, n), markdown));
+        }
+        track_var(track, idx + 1, name)
+    };
+    let items = template::Loop { len: tracks.len(), item: &item };
+    template::render_nodes_with_loop(&template::parse(list_tpl), &lookup, &items, markdown)
 }
 
 // Interpret common backslash escape sequences so users can write \n, \t, etc. on the CLI.
@@ -36,3 +117,47 @@ pub fn interpret_escapes(input: &str) -> String {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lastfm::Artist;
+
+    fn track(name: &str, artist: &str, plays: &str) -> Track {
+        Track { name: name.to_string(), playcount: plays.to_string(), artist: Artist { name: artist.to_string() }, ..Default::default() }
+    }
+
+    fn ctx() -> ListContext<'static> {
+        ListContext { username: "alice", period: "7day", date: "2024-05-01".to_string() }
+    }
+
+    #[test]
+    fn list_template_renders_entries_with_the_entry_format() {
+        let tracks = [track("Heroes", "David Bowie", "12"), track("One More Time", "Daft Punk", "30")];
+        let out = render_list_template("{#for tracks}{loop.index}) {entry}{?!loop.last}; {/}{/for}", &tracks, "{track} by {artist}", &ctx(), false);
+        assert_eq!(out, "1) Heroes by David Bowie; 2) One More Time by Daft Punk");
+        // Per-track tokens are available next to {entry}
+        let out = render_list_template("{#for tracks}{rank}:{playcount} {/for}", &tracks, "{track}", &ctx(), false);
+        assert_eq!(out, "1:12 2:30 ");
+    }
+
+    #[test]
+    fn list_template_aggregates() {
+        let tracks = [track("Heroes", "David Bowie", "12"), track("Jóga", "Björk", "30"), track("Unknown", "X", "n/a")];
+        let out = render_list_template("{username} {period} {date}: {count} tracks, {total_plays} plays", &tracks, "{track}", &ctx(), false);
+        // Play counts that are not numbers are left out of the total
+        assert_eq!(out, "alice 7day 2024-05-01: 3 tracks, 42 plays");
+        // Per-track tokens mean nothing outside the loop and are kept as written
+        assert_eq!(render_list_template("{track}", &tracks, "{track}", &ctx(), false), "{track}");
+        assert_eq!(render_list_template("[{#for tracks}{entry}{/for}]", &[], "{track}", &ctx(), false), "[]");
+    }
+
+    #[test]
+    fn markdown_list_template_leaves_entry_and_url_unescaped() {
+        let mut t = track("Bye_Bye", "*NSYNC", "5");
+        t.url = "https://www.last.fm/music/*NSYNC/_/Bye_Bye".to_string();
+        let out = render_list_template("{#for tracks}{entry} <{url}> {track}{/for}", &[t], "**{track}**", &ctx(), true);
+        // {entry} escapes its own values; {url} stays a working link; plain tokens are escaped
+        assert_eq!(out, "**Bye\\_Bye** <https://www.last.fm/music/*NSYNC/_/Bye_Bye> Bye\\_Bye");
+    }
+}
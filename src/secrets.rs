@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Environment variable consulted for the store passphrase before prompting.
+pub const PASSPHRASE_ENV: &str = "TOPSONGS_PASSPHRASE";
+
+const STORE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// On-disk format: the whole name -> value map is encrypted as one JSON blob with ChaCha20-Poly1305,
+/// keyed by Argon2id(passphrase, salt). A wrong passphrase fails authentication instead of yielding garbage.
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn store_path() -> PathBuf {
+    crate::config::config_dir().join("secrets.json")
+}
+
+pub fn store_exists() -> bool {
+    store_path().exists()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>> {
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("Corrupt secret store (invalid hex)"));
+    }
+    if !s.len().is_multiple_of(2) {
+        return Err(anyhow!("Corrupt secret store (odd-length hex)"));
+    }
+    // All ASCII now, so byte offsets are character boundaries
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| anyhow!("Corrupt secret store (invalid hex)")))
+        .collect()
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf).map_err(|e| anyhow!("Failed to gather randomness: {}", e))?;
+    Ok(buf)
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key from passphrase: {}", e))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Decrypt the store. A missing store is an empty map.
+pub fn load_secrets(passphrase: &str) -> Result<BTreeMap<String, String>> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read secret store at {}", path.display()))?;
+    decrypt_store(&content, passphrase).with_context(|| format!("Secret store at {}", path.display()))
+}
+
+fn decrypt_store(content: &str, passphrase: &str) -> Result<BTreeMap<String, String>> {
+    let file: StoreFile = serde_json::from_str(content).context("Failed to parse secret store")?;
+    if file.version != STORE_VERSION {
+        return Err(anyhow!("Unsupported secret store version {}", file.version));
+    }
+    let cipher = derive_cipher(passphrase, &from_hex(&file.salt)?)?;
+    let nonce = from_hex(&file.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(anyhow!("Corrupt secret store (bad nonce length)"));
+    }
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), from_hex(&file.ciphertext)?.as_slice())
+        .map_err(|_| anyhow!("Could not decrypt the secret store: wrong passphrase or corrupted file"))?;
+    serde_json::from_slice(&plaintext).context("Corrupt secret store contents")
+}
+
+/// Encrypt `secrets` with a fresh salt and nonce into the on-disk JSON form.
+fn encrypt_store(passphrase: &str, secrets: &BTreeMap<String, String>) -> Result<String> {
+    let salt = random_bytes::<SALT_LEN>()?;
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let cipher = derive_cipher(passphrase, &salt)?;
+    let plaintext = serde_json::to_vec(secrets)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| anyhow!("Failed to encrypt the secret store"))?;
+    let file = StoreFile { version: STORE_VERSION, salt: to_hex(&salt), nonce: to_hex(&nonce), ciphertext: to_hex(&ciphertext) };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+/// Encrypt and write the store. The file is written to a sibling created with mode 0600 and renamed into
+/// place, so it is never readable by others, not even briefly, and a failed write leaves the old store intact.
+pub fn save_secrets(passphrase: &str, secrets: &BTreeMap<String, String>) -> Result<()> {
+    let content = encrypt_store(passphrase, secrets)?;
+    let path = store_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create config directory {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    // A leftover temp file may have looser permissions; mode only applies when the file is created.
+    let _ = fs::remove_file(&tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let write = || -> std::io::Result<()> {
+        let mut file = options.open(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    };
+    write().with_context(|| format!("Failed to write secret store at {}", path.display()))
+}
+
+/// Passphrase from TOPSONGS_PASSPHRASE, or an interactive hidden prompt.
+/// `confirm` asks twice, used when creating a new store.
+pub fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(p) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(p);
+    }
+    let mut prompt = dialoguer::Password::new().with_prompt("Secret store passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation("Repeat passphrase", "Passphrases do not match");
+    }
+    prompt.interact().context("Failed to read passphrase (set TOPSONGS_PASSPHRASE when not running in a terminal)")
+}
+
+/// Read a secret from a file, trimming surrounding whitespace. A leading `~/` expands to $HOME.
+pub fn read_secret_file(path: &str) -> Result<String> {
+    let expanded = crate::config::expand_home(path);
+    let content = fs::read_to_string(&expanded)
+        .with_context(|| format!("Failed to read secret file {}", expanded.display()))?;
+    Ok(content.trim().to_string())
+}
+
+/// Run a command through the platform shell and use its trimmed stdout as the secret.
+pub fn read_secret_command(command: &str) -> Result<String> {
+    let output = if cfg!(target_os = "windows") {
+        std::process::Command::new("cmd").args(["/C", command]).output()
+    } else {
+        std::process::Command::new("sh").args(["-c", command]).output()
+    }
+    .with_context(|| format!("Failed to run secret command: {}", command))?;
+    if !output.status.success() {
+        return Err(anyhow!("Secret command exited with {}: {}", output.status, command));
+    }
+    let value = String::from_utf8(output.stdout).context("Secret command printed non-UTF-8 output")?;
+    Ok(value.trim().to_string())
+}
+
+/// Lazily unlocks the store the first time a secret is actually needed, so runs that get every
+/// value from the CLI, env or config never prompt for a passphrase.
+#[derive(Default)]
+pub struct SecretResolver {
+    unlocked: Option<BTreeMap<String, String>>,
+    failed: bool,
+}
+
+impl SecretResolver {
+    pub fn get(&mut self, name: &str) -> Option<String> {
+        if self.failed || !store_exists() {
+            return None;
+        }
+        if self.unlocked.is_none() {
+            match read_passphrase(false).and_then(|p| load_secrets(&p)) {
+                Ok(map) => self.unlocked = Some(map),
+                Err(e) => {
+                    eprintln!("Secret store not used: {:#}", e);
+                    self.failed = true;
+                    return None;
+                }
+            }
+        }
+        self.unlocked.as_ref().and_then(|m| m.get(name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> BTreeMap<String, String> {
+        BTreeMap::from([("api_key".to_string(), "abc123".to_string()), ("discord_token".to_string(), "tok ✓".to_string())])
+    }
+
+    #[test]
+    fn round_trip() {
+        let content = encrypt_store("correct horse", &sample()).unwrap();
+        assert!(!content.contains("abc123"));
+        assert_eq!(decrypt_store(&content, "correct horse").unwrap(), sample());
+        // Fresh salt and nonce every time
+        assert_ne!(content, encrypt_store("correct horse", &sample()).unwrap());
+    }
+
+    #[test]
+    fn wrong_passphrase_is_an_error() {
+        let content = encrypt_store("correct horse", &sample()).unwrap();
+        let err = decrypt_store(&content, "battery staple").unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase or corrupted file"), "{}", err);
+    }
+
+    #[test]
+    fn corrupted_hex_is_reported() {
+        let content = encrypt_store("pw", &sample()).unwrap();
+        let mut file: StoreFile = serde_json::from_str(&content).unwrap();
+        file.ciphertext.push('a');
+        let odd = serde_json::to_string(&file).unwrap();
+        assert!(decrypt_store(&odd, "pw").unwrap_err().to_string().contains("odd-length hex"));
+        file.ciphertext.push('z');
+        let invalid = serde_json::to_string(&file).unwrap();
+        assert!(decrypt_store(&invalid, "pw").unwrap_err().to_string().contains("invalid hex"));
+        // Valid hex but a flipped byte fails authentication
+        let mut file: StoreFile = serde_json::from_str(&content).unwrap();
+        let flipped = if file.ciphertext.starts_with('0') { '1' } else { '0' };
+        file.ciphertext.replace_range(0..1, &flipped.to_string());
+        let tampered = serde_json::to_string(&file).unwrap();
+        assert!(decrypt_store(&tampered, "pw").unwrap_err().to_string().contains("corrupted file"));
+        assert_eq!(from_hex("0aFf").unwrap(), [0x0a, 0xff]);
+        // Non-ASCII input must be rejected, not split mid-character
+        assert!(from_hex("aéa").unwrap_err().to_string().contains("invalid hex"));
+        assert!(from_hex("aéa0").unwrap_err().to_string().contains("invalid hex"));
+        assert!(from_hex("+1").unwrap_err().to_string().contains("invalid hex"));
+    }
+}
@@ -0,0 +1,634 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::cli::{Cli, ConflictPolicy, DiscordTarget, FitStrategy, OutputFormat, Period};
+use crate::config::{Config, Preset};
+use crate::discord::DEFAULT_BIO_LIMIT;
+
+/// Where an effective setting came from. Later layers win: built-in defaults < config file (or the selected
+/// preset, for the fields a preset bundles) < the selected config profile < `TOPSONGS_*` environment
+/// variables < command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    Config,
+    /// A key set in the selected `profile` block of the config
+    Profile(String),
+    Preset(String),
+    Env(String),
+    Cli,
+    /// Credentials only: nothing else is set, so the encrypted store is asked when the value is needed
+    SecretStore,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Config => write!(f, "config file"),
+            Origin::Profile(name) => write!(f, "profile '{}'", name),
+            Origin::Preset(name) => write!(f, "preset '{}'", name),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Cli => write!(f, "command line"),
+            Origin::SecretStore => write!(f, "secret store (if set)"),
+        }
+    }
+}
+
+/// One effective value as shown by `topsongs config show`.
+#[derive(Debug, Clone)]
+pub struct SettingOrigin {
+    /// The config key (environment variable `TOPSONGS_<KEY>`)
+    pub key: &'static str,
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// A value that can come from an environment variable and be shown back to the user.
+pub trait Setting: Sized {
+    fn parse(s: &str) -> Option<Self>;
+    fn show(&self) -> String;
+}
+
+impl Setting for String {
+    fn parse(s: &str) -> Option<Self> {
+        Some(s.to_string())
+    }
+    fn show(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+macro_rules! number_setting {
+    ($($t:ty),*) => {$(
+        impl Setting for $t {
+            fn parse(s: &str) -> Option<Self> {
+                s.trim().parse().ok()
+            }
+            fn show(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+number_setting!(u32, u64, usize);
+
+impl Setting for bool {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(true),
+            "0" | "false" | "no" | "off" => Some(false),
+            _ => None,
+        }
+    }
+    fn show(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! value_enum_setting {
+    ($($t:ty),*) => {$(
+        impl Setting for $t {
+            fn parse(s: &str) -> Option<Self> {
+                <$t as ValueEnum>::from_str(s.trim(), true).ok()
+            }
+            fn show(&self) -> String {
+                self.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+            }
+        }
+    )*};
+}
+value_enum_setting!(Period, OutputFormat, ConflictPolicy, FitStrategy, DiscordTarget);
+
+/// Comma-separated lists, e.g. `TOPSONGS_DISCORD_TARGETS=bio,status`.
+impl<T: Setting> Setting for Vec<T> {
+    fn parse(s: &str) -> Option<Self> {
+        s.split(',').map(str::trim).filter(|p| !p.is_empty()).map(T::parse).collect()
+    }
+    fn show(&self) -> String {
+        format!("[{}]", self.iter().map(Setting::show).collect::<Vec<_>>().join(", "))
+    }
+}
+
+/// Where the whole-list template comes from; inline templates still need escape handling.
+#[derive(Debug, Clone)]
+pub enum ListTemplateSource {
+    Inline(String),
+    File(String),
+}
+
+impl Setting for ListTemplateSource {
+    fn parse(s: &str) -> Option<Self> {
+        Some(ListTemplateSource::Inline(s.to_string()))
+    }
+    fn show(&self) -> String {
+        match self {
+            ListTemplateSource::Inline(t) => format!("{:?}", t),
+            ListTemplateSource::File(path) => format!("file {:?}", path),
+        }
+    }
+}
+
+/// Every setting after layering, plus where each one came from.
+#[derive(Clone)]
+pub struct Settings {
+    pub username: Option<String>,
+    pub period: Period,
+    pub limit: u32,
+    pub select: Option<usize>,
+    pub format: String,
+    pub join: String,
+    pub prefix: String,
+    pub suffix: String,
+    pub list_template: Option<ListTemplateSource>,
+    pub discord_markdown: bool,
+    pub strip_feat: bool,
+    pub strip_feat_regex: Option<String>,
+    pub move_feat: bool,
+    pub merge: bool,
+    pub transliterate: bool,
+    pub featured_separator: String,
+    pub copy: bool,
+    pub debug: bool,
+    pub table: bool,
+    pub output_format: OutputFormat,
+    pub discord_bio_regex: String,
+    pub bio_limit: usize,
+    pub bio_fit: Vec<FitStrategy>,
+    pub fallback_format: Option<String>,
+    pub update_discord: bool,
+    pub discord_dry_run: bool,
+    pub discord_confirm: bool,
+    pub on_conflict: ConflictPolicy,
+    pub discord_targets: Vec<DiscordTarget>,
+    pub status_format: String,
+    pub status_emoji: Option<String>,
+    pub status_expires: Option<String>,
+    pub pronouns_format: String,
+    pub guild_ids: Vec<String>,
+    pub webhook: bool,
+    pub webhook_embed: bool,
+    pub webhook_username: Option<String>,
+    pub bot_channel: Option<String>,
+    pub bot_post: bool,
+    pub bot_about: bool,
+    pub presence_client_id: Option<String>,
+    pub presence_details: String,
+    pub presence_state: String,
+    pub presence_interval: u64,
+    /// Problems that stop a run (e.g. an unknown preset); subcommands that don't render still work
+    pub errors: Vec<String>,
+    pub origins: Vec<SettingOrigin>,
+}
+
+const DEFAULT_FORMAT: &str = "  - {artist} - {track}";
+/// List lines also accept `\`, `*`, `~`, `` ` `` and `|` so entries escaped by `discord_markdown` still match.
+const DEFAULT_BIO_REGEX: &str = r"/\*\*[\w ]+\*\*:?\r?(\n[ \w\\*~`|-]+)+\n/";
+
+/// Environment variable for a config key: `discord_bot.channel_id` is `TOPSONGS_DISCORD_BOT_CHANNEL_ID`.
+pub fn env_name(key: &str) -> String {
+    format!("TOPSONGS_{}", key.replace('.', "_").to_uppercase())
+}
+
+/// Whether a config value for `key` came from the selected profile or the top-level settings. Profiles are
+/// applied when the config is loaded, so this only labels the value.
+fn config_origin(cfg: Option<&Config>, key: &str) -> Origin {
+    match cfg {
+        Some(Config { profile: Some(name), profile_keys, .. }) if profile_keys.iter().any(|k| k == key) => Origin::Profile(name.clone()),
+        _ => Origin::Config,
+    }
+}
+
+/// The profile to apply when loading the config: `--profile`, else `TOPSONGS_PROFILE`.
+pub fn selected_profile(cli: &Cli) -> Option<String> {
+    cli.profile.clone().or_else(|| env_layer::<String>(&process_env, "profile").1).filter(|p| !p.trim().is_empty())
+}
+
+/// Looks up an environment variable by name: the process environment normally, a fixed set in tests.
+type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// The `TOPSONGS_*` layer for `key`. Values that don't parse are reported and skipped.
+fn env_layer<T: Setting>(env: EnvLookup, key: &str) -> (Origin, Option<T>) {
+    let name = env_name(key);
+    let value = env(&name).and_then(|raw| {
+        let parsed = T::parse(&raw);
+        if parsed.is_none() {
+            eprintln!("Ignoring {}: invalid value {:?}", name, raw);
+        }
+        parsed
+    });
+    (Origin::Env(name), value)
+}
+
+struct Layers<'a> {
+    origins: Vec<SettingOrigin>,
+    cfg: Option<&'a Config>,
+    env: EnvLookup<'a>,
+}
+
+impl Layers<'_> {
+    fn env<T: Setting>(&self, key: &str) -> (Origin, Option<T>) {
+        env_layer(self.env, key)
+    }
+
+    /// Take the first layer that has a value (highest precedence first) and remember where it came from.
+    fn pick<T: Setting>(&mut self, key: &'static str, layers: Vec<(Origin, Option<T>)>) -> Option<T> {
+        let found = layers.into_iter().find_map(|(origin, v)| v.map(|v| (origin, v)));
+        let (value, origin) = match &found {
+            Some((origin, v)) => (v.show(), origin.clone()),
+            None => ("<unset>".to_string(), Origin::Default),
+        };
+        self.origins.push(SettingOrigin { key, value, origin });
+        found.map(|(_, v)| v)
+    }
+
+    fn get<T: Setting>(&mut self, key: &'static str, cli: Option<T>, cfg: Option<T>) -> Option<T> {
+        let config = config_origin(self.cfg, key);
+        self.pick(key, vec![(Origin::Cli, cli), self.env(key), (config, cfg)])
+    }
+
+    fn get_or<T: Setting>(&mut self, key: &'static str, cli: Option<T>, cfg: Option<T>, default: T) -> T {
+        let config = config_origin(self.cfg, key);
+        self.pick(key, vec![(Origin::Cli, cli), self.env(key), (config, cfg), (Origin::Default, Some(default))])
+            .expect("default layer always has a value")
+    }
+}
+
+/// An on/off flag pair such as `--copy`/`--no-copy`; clap keeps only the last one given. Neither defers to
+/// the lower layers.
+fn flag(on: bool, off: bool) -> Option<bool> {
+    if on {
+        Some(true)
+    } else if off {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Repeatable or comma-separated CLI lists count as unset when empty.
+fn list<T: Clone>(values: &[T]) -> Option<Vec<T>> {
+    (!values.is_empty()).then(|| values.to_vec())
+}
+
+/// Config values for enum settings are plain strings; unknown ones are ignored here and reported with
+/// their location by `validate::validate_config`.
+fn cfg_enum<T: ValueEnum>(value: Option<&String>) -> Option<T> {
+    T::from_str(value?.trim(), true).ok()
+}
+
+/// Comma-separated enum lists in the config; unknown entries are skipped (and reported by the validator).
+fn cfg_enum_list<T: ValueEnum>(value: Option<&String>) -> Option<Vec<T>> {
+    let items: Vec<T> = value?.split(',').map(str::trim).filter(|n| !n.is_empty()).filter_map(|name| T::from_str(name, true).ok()).collect();
+    (!items.is_empty()).then_some(items)
+}
+
+impl Settings {
+    /// Resolve every setting from the built-in defaults, the config file, `TOPSONGS_*` environment
+    /// variables and the command line, in increasing precedence.
+    pub fn resolve(cli: &Cli, cfg: Option<&Config>) -> Settings {
+        Self::resolve_with_env(cli, cfg, &process_env)
+    }
+
+    /// [`Settings::resolve`] with the environment layer read through `env`.
+    fn resolve_with_env(cli: &Cli, cfg: Option<&Config>, env: EnvLookup) -> Settings {
+        let mut l = Layers { origins: Vec::new(), cfg, env };
+        let mut errors = Vec::new();
+        let c = |f: fn(&Config) -> Option<String>| cfg.and_then(f);
+
+        l.get("profile", cli.profile.clone(), None::<String>);
+        let username = l.get("username", cli.username.clone(), c(|c| c.username.clone()));
+        let period = l.get_or("period", cli.period.clone(), cfg_enum(cfg.and_then(|c| c.period.as_ref())), Period::Overall);
+        let limit = l.get_or("limit", cli.limit, cfg.and_then(|c| c.limit), 10);
+        let select = l.get("select", cli.select, cfg.and_then(|c| c.select));
+
+        // A preset is a complete bundle: when one is selected its unset fields fall back to the defaults,
+        // not to the config, so it takes the config's place in the layers of the fields it covers. Fields the
+        // selected profile sets itself still beat the preset.
+        let preset_name = l.get("default_preset", cli.preset.clone(), c(|c| c.default_preset.clone()));
+        let preset = preset_name.as_ref().and_then(|name| {
+            let mut presets = crate::config::available_presets(cfg);
+            let found = presets.remove(name);
+            if found.is_none() {
+                let known: Vec<&str> = presets.keys().map(String::as_str).collect();
+                errors.push(format!("Unknown preset '{}'. Available presets: {}", name, known.join(", ")));
+            }
+            found
+        });
+        let middle = |key: &str, p: fn(&Preset) -> Option<String>, f: fn(&Config) -> Option<String>| match (config_origin(cfg, key), &preset_name, &preset) {
+            (origin @ Origin::Profile(_), _, _) => (origin, cfg.and_then(f)),
+            (_, Some(name), Some(preset)) => (Origin::Preset(name.clone()), p(preset)),
+            (origin, _, _) => (origin, cfg.and_then(f)),
+        };
+        let mut bundled = |key: &'static str, cli: &Option<String>, layer: (Origin, Option<String>), default: &str| {
+            l.pick(key, vec![(Origin::Cli, cli.clone()), l.env(key), layer, (Origin::Default, Some(default.to_string()))])
+                .unwrap_or_default()
+        };
+        let format = bundled("format", &cli.format, middle("format", |p| p.format.clone(), |c| c.format.clone()), DEFAULT_FORMAT);
+        let join = bundled("join", &cli.join, middle("join", |p| p.join.clone(), |c| c.join.clone()), "\n");
+        let prefix = bundled("prefix", &cli.prefix, middle("prefix", |p| p.prefix.clone(), |c| c.prefix.clone()), "");
+        let suffix = bundled("suffix", &cli.suffix, middle("suffix", |p| p.suffix.clone(), |c| c.suffix.clone()), "");
+
+        // Inline templates beat files within a layer; a selected preset replaces the config's list template.
+        let config_template = || match (c(|c| c.list_template.clone()), c(|c| c.list_template_file.clone())) {
+            (Some(inline), _) => (config_origin(cfg, "list_template"), Some(ListTemplateSource::Inline(inline))),
+            (None, Some(file)) => (config_origin(cfg, "list_template_file"), Some(ListTemplateSource::File(file))),
+            (None, None) => (Origin::Config, None),
+        };
+        let (template_origin, template) = match (config_template(), &preset_name, &preset) {
+            (layer @ (Origin::Profile(_), _), _, _) => layer,
+            (_, Some(name), Some(preset)) => (Origin::Preset(name.clone()), preset.list_template.clone().map(ListTemplateSource::Inline)),
+            (layer, _, _) => layer,
+        };
+        let env_file = env(&env_name("list_template_file")).map(ListTemplateSource::File);
+        let list_template = l.pick(
+            "list_template",
+            vec![
+                (Origin::Cli, cli.list_template.clone().map(ListTemplateSource::Inline)),
+                (Origin::Cli, cli.list_template_file.clone().map(ListTemplateSource::File)),
+                l.env("list_template"),
+                (Origin::Env(env_name("list_template_file")), env_file),
+                (template_origin, template),
+            ],
+        );
+
+        let discord_markdown = l.get_or("discord_markdown", flag(cli.discord_markdown, cli.no_discord_markdown), cfg.and_then(|c| c.discord_markdown), false);
+        let strip_feat = l.get_or("strip_feat", flag(cli.strip_feat, cli.no_strip_feat), cfg.and_then(|c| c.strip_feat), false);
+        let strip_feat_regex = l.get("strip_feat_regex", cli.strip_feat_regex.clone(), c(|c| c.strip_feat_regex.clone()));
+        let move_feat = l.get_or("move_feat", flag(cli.move_feat, cli.no_move_feat), cfg.and_then(|c| c.move_feat), false);
+        let merge = l.get_or("merge", flag(cli.merge, cli.no_merge), cfg.and_then(|c| c.merge), true);
+        let transliterate = l.get_or("transliterate", flag(cli.transliterate, cli.no_transliterate), cfg.and_then(|c| c.transliterate), false);
+        let featured_separator =
+            l.get_or("featured_separator", cli.featured_separator.clone(), c(|c| c.featured_separator.clone()), " & ".to_string());
+        let copy = l.get_or("copy", flag(cli.copy, cli.no_copy), cfg.and_then(|c| c.copy), false);
+        let debug = l.get_or("debug", flag(cli.debug, cli.no_debug), cfg.and_then(|c| c.debug), false);
+        let table = l.get_or("table", flag(cli.table, cli.no_table), cfg.and_then(|c| c.table), false);
+        let output_format =
+            l.get_or("output_format", cli.output_format, cfg_enum(cfg.and_then(|c| c.output_format.as_ref())), OutputFormat::Text);
+
+        let discord_bio_regex =
+            l.get_or("discord_bio_regex", cli.discord_bio_regex.clone(), c(|c| c.discord_bio_regex.clone()), DEFAULT_BIO_REGEX.to_string());
+        let bio_limit = l.get_or("discord_bio_limit", cli.bio_limit, cfg.and_then(|c| c.discord_bio_limit), DEFAULT_BIO_LIMIT);
+        let bio_fit = l.get_or("bio_fit", list(&cli.bio_fit), cfg_enum_list(cfg.and_then(|c| c.bio_fit.as_ref())), Vec::new());
+        let fallback_format = l.get("fallback_format", cli.fallback_format.clone(), c(|c| c.fallback_format.clone()));
+        let update_discord = l.get_or("update_discord", flag(cli.update_discord, cli.no_update_discord), cfg.and_then(|c| c.update_discord), false);
+        let discord_dry_run = l.get_or("discord_dry_run", flag(cli.discord_dry_run, cli.no_discord_dry_run), cfg.and_then(|c| c.discord_dry_run), false);
+        let discord_confirm = l.get_or("discord_confirm", flag(cli.confirm, cli.yes), cfg.and_then(|c| c.discord_confirm), true);
+        let on_conflict = l.get_or(
+            "discord_on_conflict",
+            cli.on_conflict,
+            cfg_enum(cfg.and_then(|c| c.discord_on_conflict.as_ref())),
+            ConflictPolicy::Abort,
+        );
+        let discord_targets = l.get_or(
+            "discord_targets",
+            list(&cli.discord_targets),
+            cfg_enum_list(cfg.and_then(|c| c.discord_targets.as_ref())),
+            vec![DiscordTarget::Bio],
+        );
+        let status_format = l.get_or("status_format", cli.status_format.clone(), c(|c| c.status_format.clone()), "{artist} - {track}".to_string());
+        let status_emoji = l.get("status_emoji", cli.status_emoji.clone(), c(|c| c.status_emoji.clone()));
+        let status_expires = l.get("status_expires", cli.status_expires.clone(), c(|c| c.status_expires.clone()));
+        let pronouns_format =
+            l.get_or("pronouns_format", cli.pronouns_format.clone(), c(|c| c.pronouns_format.clone()), "{artist} - {track}".to_string());
+        let guild_ids = l.get_or("discord_guild_ids", list(&cli.guild_ids), cfg.and_then(|c| c.discord_guild_ids.clone()), Vec::new());
+
+        let webhook = l.get_or("webhook", flag(cli.webhook, cli.no_webhook), cfg.and_then(|c| c.webhook), false);
+        let webhook_embed = l.get_or("webhook_embed", flag(cli.webhook_embed, cli.no_webhook_embed), cfg.and_then(|c| c.webhook_embed), false);
+        let webhook_username = l.get("webhook_username", cli.webhook_username.clone(), c(|c| c.webhook_username.clone()));
+        let bot_channel = l.get("discord_bot.channel_id", cli.bot_channel.clone(), c(|c| c.bot.channel_id.clone()));
+        let bot_post = l.get_or("discord_bot.post", flag(cli.bot_post, cli.no_bot_post), cfg.and_then(|c| c.bot.post), false);
+        let bot_about = l.get_or("discord_bot.about", flag(cli.bot_about, cli.no_bot_about), cfg.and_then(|c| c.bot.about), false);
+
+        let presence_client_id = l.get("presence_client_id", cli.presence_client_id.clone(), c(|c| c.presence_client_id.clone()));
+        let presence_details =
+            l.get_or("presence_details", cli.presence_details.clone(), c(|c| c.presence_details.clone()), "{track}".to_string());
+        let presence_state = l.get_or("presence_state", cli.presence_state.clone(), c(|c| c.presence_state.clone()), "by {artist}".to_string());
+        let presence_interval = l.get_or("presence_interval", cli.presence_interval, cfg.and_then(|c| c.presence_interval), 60);
+
+        Settings {
+            username,
+            period,
+            limit,
+            select,
+            format,
+            join,
+            prefix,
+            suffix,
+            list_template,
+            discord_markdown,
+            strip_feat,
+            strip_feat_regex,
+            move_feat,
+            merge,
+            transliterate,
+            featured_separator,
+            copy,
+            debug,
+            table,
+            output_format,
+            discord_bio_regex,
+            bio_limit,
+            bio_fit,
+            fallback_format,
+            update_discord,
+            discord_dry_run,
+            discord_confirm,
+            on_conflict,
+            discord_targets,
+            status_format,
+            status_emoji,
+            status_expires,
+            pronouns_format,
+            guild_ids,
+            webhook,
+            webhook_embed,
+            webhook_username,
+            bot_channel,
+            bot_post,
+            bot_about,
+            presence_client_id,
+            presence_details,
+            presence_state,
+            presence_interval,
+            errors,
+            origins: l.origins,
+        }
+    }
+}
+
+/// The places one credential can come from, highest precedence first (the secret store comes last).
+struct CredentialSources {
+    key: &'static str,
+    cli: Option<String>,
+    env_var: &'static str,
+    direct: Option<String>,
+    file: Option<String>,
+    command: Option<String>,
+}
+
+/// Where each credential would be read from, without reading files, running commands or unlocking the
+/// secret store. Values are never shown.
+pub fn credential_origins(cli: &Cli, cfg: Option<&Config>) -> Vec<SettingOrigin> {
+    let c = |f: fn(&Config) -> Option<String>| cfg.and_then(f);
+    let sources = [
+        CredentialSources {
+            key: "api_key",
+            cli: cli.api_key.clone(),
+            env_var: "LASTFM_API_KEY",
+            direct: c(|c| c.api_key.clone()),
+            file: c(|c| c.api_key_file.clone()),
+            command: c(|c| c.api_key_command.clone()),
+        },
+        CredentialSources {
+            key: "discord_token",
+            cli: cli.discord_token.clone(),
+            env_var: "DISCORD_TOKEN",
+            direct: c(|c| c.discord_token.clone()),
+            file: c(|c| c.discord_token_file.clone()),
+            command: c(|c| c.discord_token_command.clone()),
+        },
+        CredentialSources {
+            key: "webhook_url",
+            cli: cli.webhook_url.clone(),
+            env_var: "DISCORD_WEBHOOK_URL",
+            direct: c(|c| c.webhook_url.clone()),
+            file: None,
+            command: None,
+        },
+        CredentialSources {
+            key: "discord_bot.token",
+            cli: cli.bot_token.clone(),
+            env_var: "DISCORD_BOT_TOKEN",
+            direct: c(|c| c.bot.token.clone()),
+            file: None,
+            command: None,
+        },
+    ];
+    sources
+        .into_iter()
+        .map(|s| {
+            let (value, origin) = if s.cli.is_some() {
+                ("****".to_string(), Origin::Cli)
+            } else if std::env::var_os(s.env_var).is_some() {
+                ("****".to_string(), Origin::Env(s.env_var.to_string()))
+            } else if s.direct.is_some() {
+                ("****".to_string(), config_origin(cfg, s.key))
+            } else if let Some(path) = s.file {
+                (format!("read from file {:?}", path), config_origin(cfg, &format!("{}_file", s.key)))
+            } else if let Some(cmd) = s.command {
+                (format!("output of {:?}", cmd), config_origin(cfg, &format!("{}_command", s.key)))
+            } else {
+                ("<unset>".to_string(), Origin::SecretStore)
+            };
+            SettingOrigin { key: s.key, value, origin }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::collections::HashMap;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(std::iter::once("topsongs").chain(args.iter().copied()))
+    }
+
+    /// Resolve without any `TOPSONGS_*` variables, whatever the environment running the tests has set.
+    fn resolve(cli: &Cli, cfg: Option<&Config>) -> Settings {
+        Settings::resolve_with_env(cli, cfg, &|_| None)
+    }
+
+    fn origin(settings: &Settings, key: &str) -> Origin {
+        settings.origins.iter().find(|o| o.key == key).map(|o| o.origin.clone()).expect("every setting records its origin")
+    }
+
+    #[test]
+    fn command_line_beats_config_beats_default() {
+        let cfg = Config { limit: Some(20), copy: Some(true), ..Config::default() };
+        let s = resolve(&cli(&["--limit", "10"]), Some(&cfg));
+        assert_eq!(s.limit, 10);
+        assert_eq!(origin(&s, "limit"), Origin::Cli);
+        let s = resolve(&cli(&[]), Some(&cfg));
+        assert_eq!(s.limit, 20);
+        assert_eq!(origin(&s, "limit"), Origin::Config);
+        let s = resolve(&cli(&[]), None);
+        assert_eq!(s.limit, 10);
+        assert_eq!(origin(&s, "limit"), Origin::Default);
+        assert_eq!(origin(&s, "username"), Origin::Default);
+    }
+
+    #[test]
+    fn on_off_flags_can_turn_config_values_off() {
+        let cfg = Config { copy: Some(true), merge: Some(false), discord_confirm: Some(false), ..Config::default() };
+        let s = resolve(&cli(&[]), Some(&cfg));
+        assert!(s.copy && !s.merge && !s.discord_confirm);
+        let s = resolve(&cli(&["--no-copy", "--merge", "--confirm"]), Some(&cfg));
+        assert!(!s.copy && s.merge && s.discord_confirm);
+        assert_eq!(origin(&s, "copy"), Origin::Cli);
+        // The last of a pair wins
+        assert!(resolve(&cli(&["--no-copy", "--copy"]), None).copy);
+        assert!(!resolve(&cli(&["--copy", "--no-copy"]), None).copy);
+    }
+
+    #[test]
+    fn profile_values_are_labelled_with_the_profile() {
+        let cfg = Config {
+            limit: Some(5),
+            select: Some(3),
+            profile: Some("work".to_string()),
+            profile_keys: vec!["limit".to_string()],
+            ..Config::default()
+        };
+        let s = resolve(&cli(&[]), Some(&cfg));
+        assert_eq!((s.limit, origin(&s, "limit")), (5, Origin::Profile("work".to_string())));
+        assert_eq!((s.select, origin(&s, "select")), (Some(3), Origin::Config));
+    }
+
+    #[test]
+    fn preset_takes_the_config_place_but_not_the_profile_place() {
+        let cfg = Config {
+            format: Some("config {track}".to_string()),
+            prefix: Some("Config:\n".to_string()),
+            join: Some(" | ".to_string()),
+            suffix: Some("(config)".to_string()),
+            profile: Some("work".to_string()),
+            profile_keys: vec!["join".to_string()],
+            ..Config::default()
+        };
+        let s = resolve(&cli(&["--preset", "plain"]), Some(&cfg));
+        assert_eq!((s.format.as_str(), origin(&s, "format")), ("{rank}. {artist} - {track}", Origin::Preset("plain".to_string())));
+        // The preset leaves prefix and suffix unset, so they fall back to the defaults rather than the config
+        assert_eq!((s.prefix.as_str(), origin(&s, "prefix")), ("", Origin::Default));
+        assert_eq!(s.suffix, "");
+        // A key the selected profile sets still beats the preset
+        assert_eq!((s.join.as_str(), origin(&s, "join")), (" | ", Origin::Profile("work".to_string())));
+        assert_eq!(resolve(&cli(&[]), Some(&cfg)).format, "config {track}");
+    }
+
+    #[test]
+    fn environment_sits_between_config_and_command_line() {
+        let cfg = Config { presence_interval: Some(30), featured_separator: Some(" x ".to_string()), ..Config::default() };
+        let vars = HashMap::from([("TOPSONGS_PRESENCE_INTERVAL", "90"), ("TOPSONGS_FEATURED_SEPARATOR", " feat. ")]);
+        let env = |name: &str| vars.get(name).map(|v| v.to_string());
+        let s = Settings::resolve_with_env(&cli(&[]), Some(&cfg), &env);
+        assert_eq!(s.presence_interval, 90);
+        assert_eq!(origin(&s, "presence_interval"), Origin::Env("TOPSONGS_PRESENCE_INTERVAL".to_string()));
+        assert_eq!(s.featured_separator, " feat. ");
+
+        let s = Settings::resolve_with_env(&cli(&["--presence-interval", "120"]), Some(&cfg), &env);
+        assert_eq!((s.presence_interval, origin(&s, "presence_interval")), (120, Origin::Cli));
+
+        // An unparsable value is ignored and the next layer is used
+        let env = |name: &str| (name == "TOPSONGS_PRESENCE_INTERVAL").then(|| "soon".to_string());
+        let s = Settings::resolve_with_env(&cli(&[]), Some(&cfg), &env);
+        assert_eq!((s.presence_interval, origin(&s, "presence_interval")), (30, Origin::Config));
+    }
+}
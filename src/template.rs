@@ -0,0 +1,546 @@
+//! Small template language shared by `format`, `prefix`, `suffix`, `join` and the other per-track templates.
+//!
+//! Syntax:
+//! - `{name}` inserts a variable; `{name|filter|filter:arg}` pipes it through filters
+//!   (`upper`, `lower`, `trim`, `truncate:N`, `pad:N` (left-aligned), `lpad:N` (right-aligned), `center:N`).
+//!   Widths are terminal display columns: CJK characters and emoji count as two, and grapheme clusters
+//!   are never split.
+//! - `{?cond}...{/}` renders the body only when `cond` holds. `cond` is `name` (non-empty and not `0`),
+//!   `!name`, or `name OP value` with `OP` one of `> >= < <= == !=` (numeric when both sides are numbers).
+//! - `{{` and `}}` produce literal braces. Unknown variables are left in the output untouched.
+//! - List templates may use `{#for tracks}...{/}` to repeat the body for every entry; inside the body
+//!   the entry's own variables are available along with `loop.index` (1-based), `loop.first` and `loop.last`.
+//!   Blocks may also be closed with `{/if}` or `{/for}` for readability.
+//! - Discord Markdown filters: `bold`, `italic`, `underline`, `strike`, `spoiler`, `code`, and
+//!   `link[:label]` (`{url|link:track}` makes `[<track>](<url>)`; the label names another variable).
+//!   These always escape the value they wrap. In `markdown` mode every other value is escaped too
+//!   (`*`, `_`, `~`, `` ` ``, `|`, `\`) unless it passes through `raw`; `escape` forces escaping.
+//!   `{entry}` and `{url}` are never escaped implicitly.
+//!
+//! Rendering is forgiving: malformed tags print literally and unknown filters are skipped.
+//! [`check`] lists those problems so they can be reported as warnings.
+
+use crate::text::{pad_to_width, romanize_name, truncate_to_width, Align};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Var { name: String, filters: Vec<Filter>, raw: String },
+    If { cond: Cond, body: Vec<Node> },
+    For { body: Vec<Node> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub name: String,
+    pub arg: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cond {
+    pub name: String,
+    pub negate: bool,
+    pub cmp: Option<(CmpOp, String)>,
+}
+
+/// Parse a template into nodes. Parsing never fails: malformed tags are kept as literal text
+/// and an unclosed block runs to the end of the template. Use [`check`] to report such problems.
+pub fn parse(tpl: &str) -> Vec<Node> {
+    let mut parser = Parser { src: tpl, pos: 0, problems: Vec::new(), unclosed: false };
+    parser.nodes(false)
+}
+
+/// Problems in `tpl` that rendering silently works around: malformed or unbalanced block tags,
+/// unknown filters and filter arguments that are missing or not a number.
+pub fn check(tpl: &str) -> Vec<String> {
+    let mut parser = Parser { src: tpl, pos: 0, problems: Vec::new(), unclosed: false };
+    let nodes = parser.nodes(false);
+    let mut problems = parser.problems;
+    check_filters(&nodes, &mut problems);
+    problems
+}
+
+/// Filters that take a width, and filters that take no argument (`link` takes an optional one).
+const WIDTH_FILTERS: [&str; 4] = ["truncate", "pad", "lpad", "center"];
+const PLAIN_FILTERS: [&str; 12] =
+    ["upper", "lower", "romanize", "trim", "escape", "raw", "code", "bold", "italic", "underline", "strike", "spoiler"];
+
+fn check_filters(nodes: &[Node], problems: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Var { filters, raw, .. } => {
+                for f in filters {
+                    let arg = f.arg.as_deref().map(str::trim);
+                    let problem = if WIDTH_FILTERS.contains(&f.name.as_str()) {
+                        match arg {
+                            None => Some(format!("filter `{}` needs a width, e.g. `{}:20`", f.name, f.name)),
+                            Some(a) if a.parse::<usize>().is_err() => {
+                                Some(format!("filter `{}` needs a whole number, got `{}`", f.name, a))
+                            }
+                            Some(_) => None,
+                        }
+                    } else if PLAIN_FILTERS.contains(&f.name.as_str()) {
+                        arg.map(|a| format!("filter `{}` takes no argument, got `{}`", f.name, a))
+                    } else if f.name == "link" {
+                        None
+                    } else {
+                        Some(format!("unknown filter `{}`", f.name))
+                    };
+                    if let Some(problem) = problem {
+                        problems.push(format!("{} in `{}`", problem, raw));
+                    }
+                }
+            }
+            Node::If { body, .. } | Node::For { body } => check_filters(body, problems),
+            Node::Text(_) => {}
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Problems noticed while parsing, reported by [`check`]
+    problems: Vec<String>,
+    /// Set when a block body ran to the end of the template without a closing tag
+    unclosed: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    /// Parse nodes until the end of input or, when `in_block`, a closing `{/}` (`{/if}`, `{/for}`).
+    fn nodes(&mut self, in_block: bool) -> Vec<Node> {
+        let mut out = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.rest().chars().next() {
+            let rest = self.rest();
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push(c);
+                self.pos += 2;
+                continue;
+            }
+            if c != '{' {
+                text.push(c);
+                self.pos += c.len_utf8();
+                continue;
+            }
+            let Some(end) = rest.find('}') else {
+                text.push_str(rest);
+                self.pos = self.src.len();
+                break;
+            };
+            let inner = &rest[1..end];
+            let raw = &rest[..=end];
+            if matches!(inner, "/" | "/if" | "/for") {
+                if in_block {
+                    self.pos += end + 1;
+                    flush(&mut out, &mut text);
+                    return out;
+                }
+                self.problems.push(format!("`{}` closes a block that was never opened", raw));
+                text.push_str(raw);
+                self.pos += end + 1;
+                continue;
+            }
+            if let Some(cond_src) = inner.strip_prefix('?') {
+                self.pos += end + 1;
+                let Some(cond) = parse_cond(cond_src) else {
+                    self.problems.push(format!("`{}` is not a valid condition; expected `{{?name}}` or `{{?name OP value}}`", raw));
+                    text.push_str(raw);
+                    continue;
+                };
+                flush(&mut out, &mut text);
+                let body = self.block(raw);
+                out.push(Node::If { cond, body });
+                continue;
+            }
+            if matches!(inner.trim(), "#for" | "#for tracks") {
+                self.pos += end + 1;
+                flush(&mut out, &mut text);
+                let body = self.block(raw);
+                out.push(Node::For { body });
+                continue;
+            }
+            match parse_var(inner) {
+                Some((name, filters)) => {
+                    flush(&mut out, &mut text);
+                    out.push(Node::Var { name, filters, raw: raw.to_string() });
+                }
+                None => {
+                    if inner.starts_with('#') {
+                        self.problems.push(format!("`{}` is not a known block; only `{{#for tracks}}` is supported", raw));
+                    } else if inner.contains('|') {
+                        self.problems.push(format!("`{}` is not a valid variable with filters", raw));
+                    }
+                    text.push_str(raw);
+                }
+            }
+            self.pos += end + 1;
+        }
+        flush(&mut out, &mut text);
+        if in_block {
+            self.unclosed = true;
+        }
+        out
+    }
+
+    /// Parse the body of the block opened by `open`, noting when it is never closed.
+    fn block(&mut self, open: &str) -> Vec<Node> {
+        self.unclosed = false;
+        let body = self.nodes(true);
+        if std::mem::take(&mut self.unclosed) {
+            self.problems.push(format!("`{}` is never closed with `{{/}}`", open));
+        }
+        body
+    }
+}
+
+fn flush(out: &mut Vec<Node>, text: &mut String) {
+    if !text.is_empty() {
+        out.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+fn is_ident(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_var(inner: &str) -> Option<(String, Vec<Filter>)> {
+    let mut parts = inner.split('|');
+    let name = parts.next()?.trim();
+    if !is_ident(name) {
+        return None;
+    }
+    let mut filters = Vec::new();
+    for part in parts {
+        let (fname, arg) = match part.split_once(':') {
+            Some((n, a)) => (n.trim(), Some(a.to_string())),
+            None => (part.trim(), None),
+        };
+        if !is_ident(fname) {
+            return None;
+        }
+        filters.push(Filter { name: fname.to_string(), arg });
+    }
+    Some((name.to_string(), filters))
+}
+
+fn parse_cond(src: &str) -> Option<Cond> {
+    const OPS: [(&str, CmpOp); 6] = [
+        (">=", CmpOp::Ge),
+        ("<=", CmpOp::Le),
+        ("==", CmpOp::Eq),
+        ("!=", CmpOp::Ne),
+        (">", CmpOp::Gt),
+        ("<", CmpOp::Lt),
+    ];
+    let src = src.trim();
+    for (tok, op) in OPS {
+        if let Some(idx) = src.find(tok) {
+            let name = src[..idx].trim();
+            let value = src[idx + tok.len()..].trim().trim_matches('"');
+            return is_ident(name).then(|| Cond { name: name.to_string(), negate: false, cmp: Some((op, value.to_string())) });
+        }
+    }
+    let (negate, name) = match src.strip_prefix('!') {
+        Some(n) => (true, n.trim()),
+        None => (false, src),
+    };
+    is_ident(name).then(|| Cond { name: name.to_string(), negate, cmp: None })
+}
+
+/// The entries a `{#for}` block iterates over: `len` items whose variables come from `item(index, name)`.
+pub struct Loop<'a> {
+    pub len: usize,
+    pub item: &'a dyn Fn(usize, &str) -> Option<String>,
+}
+
+/// Variables never escaped in `markdown` mode: `{entry}` is already rendered markup, and a `{url}` with
+/// backslashes inserted would no longer be a working link.
+const UNESCAPED: [&str; 2] = ["entry", "url"];
+
+/// Render parsed nodes, resolving variables through `lookup`. `{#for}` blocks render nothing here;
+/// use [`render_nodes_with_loop`] for list templates. With `markdown`, values are escaped for Discord.
+pub fn render_nodes(nodes: &[Node], lookup: &dyn Fn(&str) -> Option<String>, markdown: bool) -> String {
+    render_inner(nodes, lookup, None, markdown)
+}
+
+/// Render parsed nodes where `{#for}` blocks iterate over `items`.
+pub fn render_nodes_with_loop(
+    nodes: &[Node],
+    lookup: &dyn Fn(&str) -> Option<String>,
+    items: &Loop,
+    markdown: bool,
+) -> String {
+    render_inner(nodes, lookup, Some(items), markdown)
+}
+
+fn render_inner(nodes: &[Node], lookup: &dyn Fn(&str) -> Option<String>, items: Option<&Loop>, markdown: bool) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(t),
+            Node::Var { name, filters, raw } => match lookup(name) {
+                Some(value) => {
+                    let escape = markdown && !UNESCAPED.contains(&name.as_str());
+                    out.push_str(&apply_filters(value, filters, escape, lookup));
+                }
+                None => out.push_str(raw),
+            },
+            Node::If { cond, body } => {
+                if eval_cond(cond, lookup) {
+                    out.push_str(&render_inner(body, lookup, items, markdown));
+                }
+            }
+            Node::For { body } => {
+                let Some(items) = items else { continue };
+                for idx in 0..items.len {
+                    let scoped = |name: &str| match name {
+                        "loop.index" => Some((idx + 1).to_string()),
+                        "loop.first" => Some(flag(idx == 0)),
+                        "loop.last" => Some(flag(idx + 1 == items.len)),
+                        _ => (items.item)(idx, name).or_else(|| lookup(name)),
+                    };
+                    // Nested loops are not supported; an inner `{#for}` renders nothing.
+                    out.push_str(&render_inner(body, &scoped, None, markdown));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Booleans are exposed as `"true"` / `""` so they work with `{?name}` and `{?!name}`.
+fn flag(b: bool) -> String {
+    if b { "true".to_string() } else { String::new() }
+}
+
+/// Parse and render `tpl` in one go, without Markdown escaping.
+pub fn render(tpl: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    render_nodes(&parse(tpl), lookup, false)
+}
+
+fn eval_cond(cond: &Cond, lookup: &dyn Fn(&str) -> Option<String>) -> bool {
+    let value = lookup(&cond.name).unwrap_or_default();
+    let result = match &cond.cmp {
+        None => !value.is_empty() && value != "0",
+        Some((op, rhs)) => {
+            let ord = match (value.trim().parse::<f64>(), rhs.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                _ => Some(value.as_str().cmp(rhs.as_str())),
+            };
+            let Some(ord) = ord else { return false };
+            match op {
+                CmpOp::Gt => ord.is_gt(),
+                CmpOp::Ge => ord.is_ge(),
+                CmpOp::Lt => ord.is_lt(),
+                CmpOp::Le => ord.is_le(),
+                CmpOp::Eq => ord.is_eq(),
+                CmpOp::Ne => ord.is_ne(),
+            }
+        }
+    };
+    result != cond.negate
+}
+
+/// Backslash-escape the characters Discord treats as Markdown inline formatting.
+pub fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Apply `filters` left to right. `escape` requests Discord Markdown escaping of the final value;
+/// markup filters escape what they wrap and mark the value as done so nothing is escaped twice.
+fn apply_filters(mut value: String, filters: &[Filter], escape: bool, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut escaped = false;
+    for f in filters {
+        let n = f.arg.as_deref().and_then(|a| a.trim().parse::<usize>().ok());
+        let wrap = match f.name.as_str() {
+            "bold" => Some("**"),
+            "italic" => Some("*"),
+            "underline" => Some("__"),
+            "strike" => Some("~~"),
+            "spoiler" => Some("||"),
+            _ => None,
+        };
+        if let Some(marker) = wrap {
+            if !escaped {
+                value = escape_markdown(&value);
+                escaped = true;
+            }
+            value = format!("{marker}{value}{marker}");
+            continue;
+        }
+        value = match (f.name.as_str(), n) {
+            ("upper", _) => value.to_uppercase(),
+            ("lower", _) => value.to_lowercase(),
+            ("romanize", _) => romanize_name(&value),
+            ("trim", _) => value.trim().to_string(),
+            ("truncate", Some(n)) => truncate_to_width(&value, n),
+            ("pad", Some(n)) => pad_to_width(&value, n, Align::Left),
+            ("lpad", Some(n)) => pad_to_width(&value, n, Align::Right),
+            ("center", Some(n)) => pad_to_width(&value, n, Align::Center),
+            ("escape", _) => {
+                if !escaped {
+                    escaped = true;
+                    escape_markdown(&value)
+                } else {
+                    value
+                }
+            }
+            ("raw", _) => {
+                escaped = true;
+                value
+            }
+            ("code", _) => {
+                escaped = true;
+                // Inline code can't escape backticks; a double-backtick fence with padding can hold single ones.
+                if value.contains('`') { format!("`` {} ``", value.replace("``", "`\u{200b}`")) } else { format!("`{}`", value) }
+            }
+            ("link", _) => {
+                escaped = true;
+                let url = value.trim().replace(' ', "%20").replace(')', "%29");
+                match f.arg.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+                    Some(label_var) => {
+                        let label = lookup(label_var).unwrap_or_else(|| label_var.to_string());
+                        let label = escape_markdown(&label).replace('[', "\\[").replace(']', "\\]");
+                        format!("[{}]({})", label, url)
+                    }
+                    // A bare link in angle brackets keeps Discord from unfurling an embed preview.
+                    None => format!("<{}>", url),
+                }
+            }
+            // Unknown filters (or missing arguments) leave the value unchanged.
+            _ => value,
+        };
+    }
+    if escape && !escaped { escape_markdown(&value) } else { value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        let value = match name {
+            "track" => "Song_Name",
+            "artist" => "宇多田ヒカル",
+            "playcount" => "42",
+            "zero" => "0",
+            "empty" => "",
+            "spaced" => "  padded  ",
+            "url" => "https://last.fm/music/a_b (live)",
+            "entry" => "**pre-rendered**",
+            _ => return None,
+        };
+        Some(value.to_string())
+    }
+
+    fn md(tpl: &str) -> String {
+        render_nodes(&parse(tpl), &vars, true)
+    }
+
+    #[test]
+    fn variables_and_literal_braces() {
+        assert_eq!(render("{track} by {artist}", &vars), "Song_Name by 宇多田ヒカル");
+        assert_eq!(render("{{track}} }} {{", &vars), "{track} } {");
+        assert_eq!(render("{missing} {not a var} {", &vars), "{missing} {not a var} {");
+    }
+
+    #[test]
+    fn text_filters() {
+        assert_eq!(render("{track|upper}|{track|lower}", &vars), "SONG_NAME|song_name");
+        assert_eq!(render("[{spaced|trim}]", &vars), "[padded]");
+        assert_eq!(render("{track|truncate:5}", &vars), "Song…");
+        assert_eq!(render("[{playcount|pad:4}][{playcount|lpad:4}][{playcount|center:6}]", &vars), "[42  ][  42][  42  ]");
+        // CJK characters are two columns wide
+        assert_eq!(render("[{artist|pad:14}]", &vars), "[宇多田ヒカル  ]");
+        assert_eq!(render("{artist|truncate:7}", &vars), "宇多田…");
+        assert_eq!(render("{artist|romanize}", &vars), "宇多田Hikaru");
+        // Filters apply left to right
+        assert_eq!(render("{spaced|trim|upper|pad:8}.", &vars), "PADDED  .");
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(render("{?playcount}yes{/}{?empty}no{/}{?zero}no{/}", &vars), "yes");
+        assert_eq!(render("{?!empty}a{/if}{?!playcount}b{/}", &vars), "a");
+        assert_eq!(render("{?playcount > 9}big{/}{?playcount < 9}small{/}", &vars), "big");
+        assert_eq!(render("{?playcount >= 42}a{/}{?playcount <= 41}b{/}{?playcount == 42}c{/}{?playcount != 42}d{/}", &vars), "ac");
+        // Numeric comparison, not lexical: "42" < "100" as numbers
+        assert_eq!(render("{?playcount < 100}num{/}", &vars), "num");
+        assert_eq!(render("{?track == \"Song_Name\"}str{/}", &vars), "str");
+        assert_eq!(render("{?playcount}outer {?empty}inner{/}done{/}", &vars), "outer done");
+    }
+
+    #[test]
+    fn for_loops() {
+        let names = ["a", "b", "c"];
+        let item = |idx: usize, name: &str| (name == "name").then(|| names[idx].to_string());
+        let items = Loop { len: names.len(), item: &item };
+        let nodes = parse("{#for tracks}{loop.index}.{name}{?loop.first}^{/}{?!loop.last}, {/}{/for} ({playcount})");
+        assert_eq!(render_nodes_with_loop(&nodes, &vars, &items, false), "1.a^, 2.b, 3.c (42)");
+        // Without a loop context the block renders nothing
+        assert_eq!(render("{#for}x{/} y", &vars), " y");
+    }
+
+    #[test]
+    fn markdown_escaping() {
+        assert_eq!(md("{track}"), "Song\\_Name");
+        assert_eq!(md("{track|raw}"), "Song_Name");
+        assert_eq!(render("{track|escape}", &vars), "Song\\_Name");
+        assert_eq!(md("{track|escape|escape}"), "Song\\_Name");
+        assert_eq!(md("{entry}"), "**pre-rendered**");
+        assert_eq!(md("{url}"), "https://last.fm/music/a_b (live)");
+        assert_eq!(md("{url|escape}"), "https://last.fm/music/a\\_b (live)");
+        assert_eq!(escape_markdown("a*b_c~d`e|f\\g"), "a\\*b\\_c\\~d\\`e\\|f\\\\g");
+    }
+
+    #[test]
+    fn markup_filters_escape_once() {
+        assert_eq!(render("{track|bold}", &vars), "**Song\\_Name**");
+        assert_eq!(md("{track|italic}{track|underline}"), "*Song\\_Name*__Song\\_Name__");
+        assert_eq!(md("{track|strike|spoiler}"), "||~~Song\\_Name~~||");
+        assert_eq!(md("{track|code}"), "`Song_Name`");
+        assert_eq!(render("{x|code}", &|_| Some("a`b".to_string())), "`` a`b ``");
+        assert_eq!(md("{url|link:track}"), "[Song\\_Name](https://last.fm/music/a_b%20(live%29)");
+        assert_eq!(md("{url|link}"), "<https://last.fm/music/a_b%20(live%29>");
+    }
+
+    #[test]
+    fn check_reports_problems() {
+        assert!(check("{track|upper|pad:20} {?playcount > 5}{url|link:track}{/}{{x}}").is_empty());
+        assert_eq!(check("{track|shout}"), ["unknown filter `shout` in `{track|shout}`"]);
+        assert_eq!(check("{track|pad:abc}"), ["filter `pad` needs a whole number, got `abc` in `{track|pad:abc}`"]);
+        assert_eq!(check("{track|truncate}"), ["filter `truncate` needs a width, e.g. `truncate:20` in `{track|truncate}`"]);
+        assert_eq!(check("{track|upper:3}"), ["filter `upper` takes no argument, got `3` in `{track|upper:3}`"]);
+        let problems = check("{?}x{/}");
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("`{?}` is not a valid condition"));
+        assert!(problems[1].contains("`{/}` closes a block that was never opened"));
+        assert_eq!(check("{?playcount}x"), ["`{?playcount}` is never closed with `{/}`"]);
+        assert_eq!(check("{#each}x{/}").len(), 2);
+        assert_eq!(check("{track|bad filter}"), ["`{track|bad filter}` is not a valid variable with filters"]);
+        // Problems inside blocks are found too
+        assert_eq!(check("{#for tracks}{track|nope}{/}"), ["unknown filter `nope` in `{track|nope}`"]);
+    }
+}
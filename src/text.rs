@@ -1,4 +1,8 @@
-﻿use regex::Regex;
+﻿use std::sync::OnceLock;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 /// Remove wrapping slashes if present (e.g., "/abc/" -> "abc").
 pub fn normalize_pattern(p: &str) -> String {
@@ -10,23 +14,809 @@ pub fn normalize_pattern(p: &str) -> String {
     }
 }
 
-/// Strip featured-artist annotations from a track title using either a custom regex or a sensible default.
-/// After stripping, leading/trailing whitespace and dashes are trimmed.
-pub fn strip_title(title: &str, custom_regex: Option<&str>) -> String {
-    // Default pattern removes things like:
-    //  - "(feat. Artist)" or "[ft. Artist]" anywhere
-    //  - trailing "- feat. Artist" or "- with Artist"
-    // Case-insensitive, remove from the end when it matches those patterns.
-    let default_pat = r"(?i)\s*(?:[\(\[]\s*(?:feat\.?|ft\.?|with)\b.*?[\)\]]|-\s*(?:feat\.?|ft\.?|with)\b.*)$";
-    let pat = custom_regex
-        .map(|r| normalize_pattern(r))
-        .unwrap_or_else(|| default_pat.to_string());
-
-    let re = Regex::new(&pat).unwrap_or_else(|_| Regex::new(default_pat).expect("default regex compiles"));
-    let stripped = re.replace(title, "").to_string();
-    // Trim common surrounding spaces and separators left behind
-    let stripped = stripped.trim();
-    // Also trim a trailing dash or colon if left at the end after removal
-    let stripped = stripped.trim_end_matches(['-', ':', '–', '—', '|', '/']).trim();
-    stripped.to_string()
+/// Default pattern for the `feat` rule. Removes things like:
+///  - "(feat. Artist)" or "[ft. Artist]" at the end
+///  - trailing "- feat. Artist" or "- with Artist"
+pub const FEAT_PATTERN: &str = r"(?i)\s*(?:[\(\[]\s*(?:feat\.?|ft\.?|with)\b.*?[\)\]]|-\s*(?:feat\.?|ft\.?|with)\b.*)$";
+
+/// Built-in rules that can be referenced by name from the `cleanup` block, with their patterns.
+/// `collapse_whitespace` is not a regex rule and is handled separately. Each category only matches
+/// bracketed tags or dash-separated suffixes, so titles like "Live Forever" or "Edit the World" survive.
+/// Remixes credited to someone ("- Skrillex Remix") are deliberately kept: they are different tracks.
+/// A dash-separated suffix needs whitespace before the dash ("Self-Edit" is a title) and has to end the title
+/// or be followed by another suffix; that following separator is captured as group 1 and put back, which is
+/// why built-in rules replace with `${1}` by default.
+/// Suggested order: feat, remaster, live, edit_mix, edition, explicit, collapse_whitespace.
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("feat", FEAT_PATTERN),
+    // "- 2015 Remaster", "- Remastered 2011", "- 2019 - Remaster", "/ Remastered 2015", "(Remastered)",
+    // "[2015 Paisley Park Remaster]", "- 50th Anniversary Remastered Version"
+    (
+        "remaster",
+        r"(?i)\s*(?:\s+[-/]\s*(?:\d{4}\s*(?:-\s*)?)?(?:\d+(?:st|nd|rd|th)\s+anniversary\s+)?(?:digital(?:ly)?\s+)?remaster(?:ed)?(?:\s+\d{4})?(?:\s+(?:version|edition|mix))?(?:\s*$|(\s+[-/]\s|\s*[\(\[]))|[\(\[][^()\[\]]*\bremaster(?:ed)?\b[^()\[\]]*[\)\]])",
+    ),
+    // "(Live at Wembley)", "[Live]", "(Live Version)", "(Recorded Live in Tokyo)", "- Live", "- Live at Budokan 1978"
+    (
+        "live",
+        r"(?i)\s*(?:[\(\[]\s*(?:recorded\s+)?live(?:\s+(?:at|from|in|on)\b[^()\[\]]*|\s+(?:version|recording|session|edit|take))?\s*[\)\]]|-\s*(?:recorded\s+)?live(?:\s+(?:at|from|in|on)\b[^()\[\]]*|\s+(?:version|recording|session|edit|take))?\s*$)",
+    ),
+    // "- Radio Edit", "(Single Version)", "- Album Version", "(Extended Mix)", "(Original Mix)", "- Edit", "- Mono"
+    (
+        "edit_mix",
+        r"(?i)\s*(?:\s+-\s*(?:(?:radio|single|album|clean|short|extended|original|main|full[\s-]length)\s+(?:edit|version|mix|cut)|edit|(?:mono|stereo)(?:\s+(?:version|mix))?)(?:\s*$|(\s+[-/]\s|\s*[\(\[]))|[\(\[]\s*(?:(?:radio|single|album|clean|short|extended|original|main|full[\s-]length)\s+(?:edit|version|mix|cut)|edit|(?:mono|stereo)(?:\s+(?:version|mix))?)\s*[\)\]])",
+    ),
+    // "(Deluxe Edition)", "(Super Deluxe)", "(10th Anniversary Edition)", "(Taylor's Version)", "[From the Vault]",
+    // "(Bonus Track)", "- Deluxe Edition"
+    (
+        "edition",
+        r"(?i)\s*(?:[\(\[]\s*(?:(?:(?:super\s+)?deluxe|expanded|special|limited|collector['’]?s|(?:\d+(?:st|nd|rd|th)\s+)?anniversary|platinum|legacy|japanese|japan)(?:\s+(?:edition|version))?|taylor['’]s\s+version|from\s+the\s+vault|bonus(?:\s+track)?)\s*[\)\]]|\s+-\s*(?:(?:(?:super\s+)?deluxe|expanded|special|limited|(?:\d+(?:st|nd|rd|th)\s+)?anniversary)\s+edition|bonus\s+track)(?:\s*$|(\s+[-/]\s|\s*[\(\[])))",
+    ),
+    // "[Explicit]", "(Explicit Version)", "(Clean)", "(Dirty)", "- Explicit"
+    (
+        "explicit",
+        r"(?i)\s*(?:[\(\[]\s*(?:explicit|clean|dirty)(?:\s+version)?\s*[\)\]]|-\s*explicit(?:\s+version)?\s*$)",
+    ),
+];
+
+/// Names accepted by `rule "<name>"` without a `pattern`.
+pub fn builtin_rule_names() -> Vec<&'static str> {
+    BUILTIN_RULES.iter().map(|(n, _)| *n).chain(["collapse_whitespace"]).collect()
+}
+
+/// One rule as written in the config: a built-in name, or a custom `pattern`/`replace` pair.
+#[derive(Debug, Clone)]
+pub struct RuleSpec {
+    pub name: String,
+    pub enabled: bool,
+    pub pattern: Option<String>,
+    pub replace: Option<String>,
+}
+
+#[derive(Debug)]
+enum RuleAction {
+    Regex { re: Regex, replace: String },
+    CollapseWhitespace,
+}
+
+#[derive(Debug)]
+pub struct CleanupRule {
+    pub name: String,
+    pub enabled: bool,
+    action: RuleAction,
+}
+
+impl CleanupRule {
+    fn apply(&self, title: &str) -> String {
+        match &self.action {
+            RuleAction::Regex { re, replace } => tidy(&re.replace_all(title, replace.as_str())),
+            RuleAction::CollapseWhitespace => title.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+/// Trim whitespace and the separators a removed suffix tends to leave dangling at the end.
+fn tidy(s: &str) -> String {
+    s.trim().trim_end_matches(['-', ':', '–', '—', '|', '/']).trim().to_string()
+}
+
+/// Ordered list of title cleanup rules. Disabled rules are kept so `topsongs text test` can list them.
+#[derive(Debug, Default)]
+pub struct Cleanup {
+    pub rules: Vec<CleanupRule>,
+}
+
+impl Cleanup {
+    /// Compile `specs` in order. Every invalid regex or unknown built-in name is reported, not just the first.
+    pub fn from_specs(specs: &[RuleSpec]) -> Result<Cleanup, Vec<String>> {
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs {
+            let action = match (&spec.pattern, spec.name.as_str()) {
+                (Some(p), _) => match Regex::new(&normalize_pattern(p)) {
+                    Ok(re) => RuleAction::Regex { re, replace: spec.replace.clone().unwrap_or_default() },
+                    Err(e) => {
+                        errors.push(format!("cleanup rule '{}': invalid regex: {}", spec.name, e));
+                        continue;
+                    }
+                },
+                (None, "collapse_whitespace") => RuleAction::CollapseWhitespace,
+                (None, name) => match BUILTIN_RULES.iter().find(|(n, _)| *n == name) {
+                    Some((_, pat)) => RuleAction::Regex {
+                        re: Regex::new(pat).expect("built-in cleanup regex compiles"),
+                        replace: spec.replace.clone().unwrap_or_else(|| "${1}".to_string()),
+                    },
+                    None => {
+                        errors.push(format!(
+                            "cleanup rule '{}' is not built in and has no pattern (built-in rules: {})",
+                            name,
+                            builtin_rule_names().join(", ")
+                        ));
+                        continue;
+                    }
+                },
+            };
+            rules.push(CleanupRule { name: spec.name.clone(), enabled: spec.enabled, action });
+        }
+        if errors.is_empty() { Ok(Cleanup { rules }) } else { Err(errors) }
+    }
+
+    /// Run every enabled rule in order.
+    pub fn apply(&self, title: &str) -> String {
+        self.rules.iter().filter(|r| r.enabled).fold(title.to_string(), |acc, r| r.apply(&acc))
+    }
+
+    /// The title after each rule, in order; disabled rules yield `None` and leave the title unchanged.
+    pub fn trace(&self, title: &str) -> Vec<(&CleanupRule, Option<String>)> {
+        let mut current = title.to_string();
+        self.rules
+            .iter()
+            .map(|r| {
+                if !r.enabled {
+                    return (r, None);
+                }
+                current = r.apply(&current);
+                (r, Some(current.clone()))
+            })
+            .collect()
+    }
+}
+
+/// `title` with the built-in remaster, edit/mix and edition tags removed, whatever the configured cleanup
+/// keeps. Live recordings and remixes stay distinct.
+pub fn strip_version_tags(title: &str) -> String {
+    static RULES: OnceLock<Cleanup> = OnceLock::new();
+    let rules = RULES.get_or_init(|| {
+        let specs: Vec<RuleSpec> = ["remaster", "edit_mix", "edition"]
+            .iter()
+            .map(|name| RuleSpec { name: name.to_string(), enabled: true, pattern: None, replace: None })
+            .collect();
+        Cleanup::from_specs(&specs).expect("built-in cleanup rules compile")
+    });
+    rules.apply(title)
+}
+
+/// Featured-artist credits inside brackets anywhere in the title ("(feat. X)", "[ft. X & Y]") or after a
+/// trailing dash ("- feat. X", "- with X"). The credit itself is captured. A bracketed "with" is not a credit:
+/// "Stand by Me (With Strings)" names an arrangement, not an artist.
+const FEATURED_PATTERN: &str = r"(?i)\s*(?:[\(\[]\s*(?:feat\.?|ft\.?|featuring)\s+([^()\[\]]+?)\s*[\)\]]|\s-\s*(?:feat\.?|ft\.?|featuring|with)\s+([^()\[\]]+?)\s*$)";
+
+/// Split a credit like "A, B & C" or "A feat. B" into individual names, dropping empties and duplicates.
+/// Only unambiguous separators are used (`,`, `&`, `;`, `feat.`/`ft.`): " and " or " x " are too often part
+/// of a name ("Florence and the Machine").
+pub fn split_artists(credit: &str) -> Vec<String> {
+    static SEPARATORS: OnceLock<Regex> = OnceLock::new();
+    let re = SEPARATORS.get_or_init(|| {
+        Regex::new(r"(?i)\s*(?:,|&|;|\bfeat\.?\s|\bft\.?\s|\bfeaturing\s)\s*").expect("artist split regex compiles")
+    });
+    let mut names: Vec<String> = Vec::new();
+    for name in re.split(credit).map(str::trim).filter(|n| !n.is_empty()) {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Pull featured artists out of `title`. Returns the title without the credit and the featured names,
+/// excluding any that repeat the main `artist`.
+pub fn extract_featured(title: &str, artist: &str) -> (String, Vec<String>) {
+    static FEATURED: OnceLock<Regex> = OnceLock::new();
+    let re = FEATURED.get_or_init(|| Regex::new(FEATURED_PATTERN).expect("featured regex compiles"));
+    let mut featured: Vec<String> = Vec::new();
+    for caps in re.captures_iter(title) {
+        let credit = caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str()).unwrap_or_default();
+        for name in split_artists(credit) {
+            if !name.eq_ignore_ascii_case(artist) && !featured.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+                featured.push(name);
+            }
+        }
+    }
+    if featured.is_empty() {
+        return (title.to_string(), featured);
+    }
+    (tidy(&re.replace_all(title, "")), featured)
+}
+
+/// Terminal display width of `s`: East Asian wide characters and most emoji take two columns,
+/// combining marks and zero-width joiners take none.
+pub fn display_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Width of one grapheme cluster. Emoji sequences (ZWJ, skin tones, flags) are measured as one
+/// double-width glyph instead of the sum of their parts.
+fn grapheme_width(g: &str) -> usize {
+    let w = g.width();
+    if g.chars().count() > 1 && w > 2 { 2 } else { w }
+}
+
+/// Cut `s` to at most `max_width` display columns without splitting a grapheme cluster,
+/// ending in an ellipsis (one column) when anything was removed.
+pub fn truncate_to_width(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+    let budget = max_width.saturating_sub(1);
+    let mut out = String::new();
+    let mut used = 0;
+    for g in s.graphemes(true) {
+        let w = grapheme_width(g);
+        if used + w > budget {
+            break;
+        }
+        out.push_str(g);
+        used += w;
+    }
+    let mut out = out.trim_end().to_string();
+    if max_width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Pad `s` with spaces to `width` display columns. Text that is already wider is returned unchanged.
+pub fn pad_to_width(s: &str, width: usize, align: Align) -> String {
+    let fill = width.saturating_sub(display_width(s));
+    let (left, right) = match align {
+        Align::Left => (0, fill),
+        Align::Right => (fill, 0),
+        Align::Center => (fill / 2, fill - fill / 2),
+    };
+    format!("{}{}{}", " ".repeat(left), s, " ".repeat(right))
+}
+
+/// Keep at most `max_chars` code points of `s` without splitting a grapheme cluster.
+pub fn take_chars(s: &str, max_chars: usize) -> &str {
+    let mut end = 0;
+    let mut count = 0;
+    for (idx, g) in s.grapheme_indices(true) {
+        let n = g.chars().count();
+        if count + n > max_chars {
+            break;
+        }
+        count += n;
+        end = idx + g.len();
+    }
+    &s[..end]
+}
+
+/// Official romanised names from the config's `romanized_names` block. Set once at startup so the
+/// `romanize` template filter sees them as well.
+static ROMANIZED_NAMES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// Register the per-artist overrides used by [`romanize_name`]. Only the first call has any effect.
+pub fn set_romanized_names(names: Vec<(String, String)>) {
+    let _ = ROMANIZED_NAMES.set(names);
+}
+
+/// The official spelling when the whole of `name` (ignoring case and surrounding space) is listed in
+/// `romanized_names`, otherwise [`romanize`]. Overrides never apply to part of a name or title, so
+/// "Kino" stays "Kino" inside "Kinoteatr" and a song that merely mentions an artist is transliterated as usual.
+pub fn romanize_name(name: &str) -> String {
+    let key = name.trim().to_lowercase();
+    ROMANIZED_NAMES
+        .get()
+        .and_then(|names| names.iter().find(|(from, _)| from.trim().to_lowercase() == key))
+        .map(|(_, to)| to.clone())
+        .unwrap_or_else(|| romanize(name))
+}
+
+/// Russian, Ukrainian, Belarusian and Serbian letters (lowercase), roughly following BGN/PCGN.
+const CYRILLIC: &[(char, &str)] = &[
+    ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('д', "d"), ('е', "e"), ('ё', "yo"), ('ж', "zh"),
+    ('з', "z"), ('и', "i"), ('й', "y"), ('к', "k"), ('л', "l"), ('м', "m"), ('н', "n"), ('о', "o"),
+    ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"), ('ф', "f"), ('х', "kh"), ('ц', "ts"),
+    ('ч', "ch"), ('ш', "sh"), ('щ', "shch"), ('ъ', ""), ('ы', "y"), ('ь', ""), ('э', "e"), ('ю', "yu"),
+    ('я', "ya"), ('і', "i"), ('ї', "yi"), ('є', "ye"), ('ґ', "g"), ('ў', "u"), ('ђ', "dj"), ('ј', "j"),
+    ('љ', "lj"), ('њ', "nj"), ('ћ', "c"), ('џ', "dz"),
+];
+
+/// Modern Greek letters (lowercase, with and without accents).
+const GREEK: &[(char, &str)] = &[
+    ('α', "a"), ('β', "v"), ('γ', "g"), ('δ', "d"), ('ε', "e"), ('ζ', "z"), ('η', "i"), ('θ', "th"),
+    ('ι', "i"), ('κ', "k"), ('λ', "l"), ('μ', "m"), ('ν', "n"), ('ξ', "x"), ('ο', "o"), ('π', "p"),
+    ('ρ', "r"), ('σ', "s"), ('ς', "s"), ('τ', "t"), ('υ', "y"), ('φ', "f"), ('χ', "ch"), ('ψ', "ps"),
+    ('ω', "o"), ('ά', "a"), ('έ', "e"), ('ή', "i"), ('ί', "i"), ('ό', "o"), ('ύ', "y"), ('ώ', "o"),
+    ('ϊ', "i"), ('ϋ', "y"), ('ΐ', "i"), ('ΰ', "y"),
+];
+
+/// Hiragana in Hepburn; katakana is mapped onto these first. Small kana and っ/ん are combined with
+/// their neighbours in [`kana`].
+const KANA: &[(char, &str)] = &[
+    ('あ', "a"), ('い', "i"), ('う', "u"), ('え', "e"), ('お', "o"),
+    ('か', "ka"), ('き', "ki"), ('く', "ku"), ('け', "ke"), ('こ', "ko"),
+    ('が', "ga"), ('ぎ', "gi"), ('ぐ', "gu"), ('げ', "ge"), ('ご', "go"),
+    ('さ', "sa"), ('し', "shi"), ('す', "su"), ('せ', "se"), ('そ', "so"),
+    ('ざ', "za"), ('じ', "ji"), ('ず', "zu"), ('ぜ', "ze"), ('ぞ', "zo"),
+    ('た', "ta"), ('ち', "chi"), ('つ', "tsu"), ('て', "te"), ('と', "to"),
+    ('だ', "da"), ('ぢ', "ji"), ('づ', "zu"), ('で', "de"), ('ど', "do"),
+    ('な', "na"), ('に', "ni"), ('ぬ', "nu"), ('ね', "ne"), ('の', "no"),
+    ('は', "ha"), ('ひ', "hi"), ('ふ', "fu"), ('へ', "he"), ('ほ', "ho"),
+    ('ば', "ba"), ('び', "bi"), ('ぶ', "bu"), ('べ', "be"), ('ぼ', "bo"),
+    ('ぱ', "pa"), ('ぴ', "pi"), ('ぷ', "pu"), ('ぺ', "pe"), ('ぽ', "po"),
+    ('ま', "ma"), ('み', "mi"), ('む', "mu"), ('め', "me"), ('も', "mo"),
+    ('や', "ya"), ('ゆ', "yu"), ('よ', "yo"),
+    ('ら', "ra"), ('り', "ri"), ('る', "ru"), ('れ', "re"), ('ろ', "ro"),
+    ('わ', "wa"), ('ゐ', "wi"), ('ゑ', "we"), ('を', "o"), ('ん', "n"), ('ゔ', "vu"),
+    ('ぁ', "a"), ('ぃ', "i"), ('ぅ', "u"), ('ぇ', "e"), ('ぉ', "o"),
+    ('ゃ', "ya"), ('ゅ', "yu"), ('ょ', "yo"), ('ゎ', "wa"), ('っ', ""),
+    ('ヷ', "va"), ('ヸ', "vi"), ('ヹ', "ve"), ('ヺ', "vo"),
+];
+
+const HANGUL_INITIALS: [&str; 19] = ["g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p", "h"];
+const HANGUL_VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we", "wi", "yu", "eu", "ui", "i",
+];
+const HANGUL_FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p", "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+fn lookup(table: &[(char, &'static str)], c: char) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == c).map(|(_, v)| *v)
+}
+
+/// Latin for an uppercase letter keeps its case: "Ж" is "Zh", or "ZH" inside an all-caps word.
+fn cased(c: char, latin: &str, next: Option<char>) -> String {
+    if !c.is_uppercase() {
+        latin.to_string()
+    } else if next.is_some_and(char::is_uppercase) {
+        latin.to_uppercase()
+    } else {
+        capitalize(latin)
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Revised Romanization of one Hangul syllable, letter by letter (no sound-change rules).
+fn hangul(c: char) -> Option<String> {
+    let idx = (c as u32).checked_sub(0xAC00).filter(|i| *i < 11172)? as usize;
+    Some(format!("{}{}{}", HANGUL_INITIALS[idx / 588], HANGUL_VOWELS[idx % 588 / 28], HANGUL_FINALS[idx % 28]))
+}
+
+/// Romanise the kana at the start of `chars`, together with a following small kana ("きゃ" is "kya",
+/// "ティ" is "ti"). Returns the Latin text and how many characters were used.
+fn kana(chars: &[char]) -> Option<(String, usize)> {
+    let first = to_hiragana(*chars.first()?);
+    let base = lookup(KANA, first).or_else(|| lookup(KANA, chars[0]))?;
+    match first {
+        // Small tsu doubles the next consonant ("matte", "matcha").
+        'っ' => {
+            let (next, used) = kana(&chars[1..]).unwrap_or_default();
+            let doubled = match next.chars().next() {
+                Some(_) if next.starts_with("ch") => format!("t{}", next),
+                Some(c) if !"aeiouyn".contains(c) => format!("{}{}", c, next),
+                _ => next,
+            };
+            return Some((doubled, used + 1));
+        }
+        // Syllabic n is written n' before a vowel or y so "kan'i" doesn't read as "ka-ni".
+        'ん' => {
+            let apostrophe = kana(&chars[1..]).is_some_and(|(next, _)| next.starts_with(['a', 'e', 'i', 'o', 'u', 'y']));
+            return Some((if apostrophe { "n'" } else { "n" }.to_string(), 1));
+        }
+        _ => {}
+    }
+    let stem = base.strip_suffix(['a', 'i', 'u', 'e', 'o']).unwrap_or(base);
+    let combined = match chars.get(1).map(|c| to_hiragana(*c)) {
+        Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if !stem.is_empty() => {
+            let vowel = &lookup(KANA, small).unwrap_or_default()[1..];
+            if stem.ends_with("sh") || stem.ends_with("ch") || stem.ends_with('j') {
+                format!("{}{}", stem, vowel)
+            } else {
+                format!("{}y{}", stem, vowel)
+            }
+        }
+        Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) => {
+            let stem = if stem.is_empty() { "w" } else { stem };
+            format!("{}{}", stem, lookup(KANA, small).unwrap_or_default())
+        }
+        _ => return Some((base.to_string(), 1)),
+    };
+    Some((combined, 2))
+}
+
+/// CJK punctuation and full-width forms with a plain ASCII equivalent.
+fn push_ascii_punctuation(out: &mut String, c: char) {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => out.push(char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)),
+        '\u{3000}' | '・' => out.push(' '),
+        '、' => out.push_str(", "),
+        '。' => out.push('.'),
+        '「' | '」' | '『' | '』' => out.push('"'),
+        '〜' => out.push('~'),
+        _ => out.push(c),
+    }
+}
+
+/// Transliterate Cyrillic, Greek, Japanese kana and Hangul to Latin letters. Kanji and Hanzi can't be read
+/// without a dictionary and are kept, as is anything already Latin; artists written that way need an
+/// override in `romanized_names`, applied by [`romanize_name`].
+pub fn romanize(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::with_capacity(s.len());
+    // Kana and Hangul have no case; each run of them is capitalised like a word.
+    let mut caseless_run = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        // Greek "ου" is one vowel, "ou" (Μουσική is "Mousiki", not "Moysiki").
+        if lower == 'ο' && chars.get(i + 1).is_some_and(|n| matches!(n.to_lowercase().next(), Some('υ' | 'ύ'))) {
+            out.push_str(&cased(c, "ou", chars.get(i + 1).copied()));
+            caseless_run = false;
+            i += 2;
+            continue;
+        }
+        if let Some(latin) = lookup(CYRILLIC, lower).or_else(|| lookup(GREEK, lower)) {
+            out.push_str(&cased(c, latin, chars.get(i + 1).copied()));
+            caseless_run = false;
+            i += 1;
+            continue;
+        }
+        // The katakana long-vowel mark repeats the previous vowel.
+        if c == 'ー' && caseless_run {
+            if let Some(vowel) = out.chars().last().filter(|v| "aeiou".contains(*v)) {
+                out.push(vowel);
+            }
+            i += 1;
+            continue;
+        }
+        let syllable = hangul(c).map(|h| (h, 1)).or_else(|| kana(&chars[i..]));
+        if let Some((latin, used)) = syllable {
+            out.push_str(&if caseless_run { latin } else { capitalize(&latin) });
+            caseless_run = !out.is_empty();
+            i += used;
+            continue;
+        }
+        push_ascii_punctuation(&mut out, c);
+        caseless_run = false;
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(names: &[&str]) -> Cleanup {
+        let specs: Vec<RuleSpec> = names
+            .iter()
+            .map(|n| RuleSpec { name: n.to_string(), enabled: true, pattern: None, replace: None })
+            .collect();
+        Cleanup::from_specs(&specs).expect("built-in rules compile")
+    }
+
+    /// Run each `(input, expected)` pair through a pipeline made of `rules` and report every mismatch at once.
+    fn check(rules: &[&str], cases: &[(&str, &str)]) {
+        let cleanup = pipeline(rules);
+        let failures: Vec<String> = cases
+            .iter()
+            .filter_map(|(input, expected)| {
+                let got = cleanup.apply(input);
+                (got != *expected).then(|| format!("{:?}: expected {:?}, got {:?}", input, expected, got))
+            })
+            .collect();
+        assert!(failures.is_empty(), "{:?} mismatches:\n{}", rules, failures.join("\n"));
+    }
+
+    const ALL: &[&str] = &["feat", "remaster", "live", "edit_mix", "edition", "explicit", "collapse_whitespace"];
+
+    #[test]
+    fn feat() {
+        check(
+            &["feat"],
+            &[
+                ("One More Time (feat. Romanthony)", "One More Time"),
+                ("Stay [ft. Justin Bieber]", "Stay"),
+                ("Old Town Road - feat. Billy Ray Cyrus", "Old Town Road"),
+                ("Under Pressure - with David Bowie", "Under Pressure"),
+                ("Bad Habits (Feat Ed Sheeran)", "Bad Habits"),
+                ("Without Me", "Without Me"),
+                ("Feathers", "Feathers"),
+            ],
+        );
+    }
+
+    #[test]
+    fn remaster() {
+        check(
+            &["remaster"],
+            &[
+                ("Bohemian Rhapsody - Remastered 2011", "Bohemian Rhapsody"),
+                ("Heroes - 2017 Remaster", "Heroes"),
+                ("Wish You Were Here - 2011 Remastered Version", "Wish You Were Here"),
+                ("Paint It, Black - Remastered", "Paint It, Black"),
+                ("Here Comes the Sun - Remastered 2009", "Here Comes the Sun"),
+                ("Dreams - 2004 Remaster", "Dreams"),
+                ("Born to Run - 2019 - Remaster", "Born to Run"),
+                ("Let It Be (Remastered)", "Let It Be"),
+                ("Hotel California (2013 Remaster)", "Hotel California"),
+                ("Purple Rain [2015 Paisley Park Remaster]", "Purple Rain"),
+                ("Space Oddity [2009 Digital Remaster]", "Space Oddity"),
+                ("Paranoid Android - Remastered Version", "Paranoid Android"),
+                ("Come Together - 50th Anniversary Remastered Version", "Come Together"),
+                ("Africa (Digitally Remastered)", "Africa"),
+                ("Song - Remastered 2011 (Live at Wembley)", "Song (Live at Wembley)"),
+                // Not remaster tags
+                ("Remastered Memories", "Remastered Memories"),
+                ("The Remaster Plan", "The Remaster Plan"),
+                ("Song - Remastered Memories", "Song - Remastered Memories"),
+                ("Un-Remastered", "Un-Remastered"),
+                ("Heroes - 2017 Remaster - Single Version", "Heroes - Single Version"),
+            ],
+        );
+    }
+
+    #[test]
+    fn live() {
+        check(
+            &["live"],
+            &[
+                ("Bohemian Rhapsody (Live at Wembley '86)", "Bohemian Rhapsody"),
+                ("Hotel California [Live]", "Hotel California"),
+                ("Creep (Live Version)", "Creep"),
+                ("Layla (Recorded Live in Tokyo)", "Layla"),
+                ("I Want You - Live", "I Want You"),
+                ("Comfortably Numb - Live at Earls Court 1980", "Comfortably Numb"),
+                ("Smells Like Teen Spirit (Live On MTV Unplugged)", "Smells Like Teen Spirit"),
+                ("Hallelujah - Live from Sin-é", "Hallelujah"),
+                ("Heart of Glass (live session)", "Heart of Glass"),
+                // Not live tags
+                ("Live Forever", "Live Forever"),
+                ("Live and Let Die", "Live and Let Die"),
+                ("Livin' on a Prayer", "Livin' on a Prayer"),
+                ("Song - Live Wire", "Song - Live Wire"),
+                ("(I Just) Died in Your Arms", "(I Just) Died in Your Arms"),
+            ],
+        );
+    }
+
+    #[test]
+    fn edit_mix() {
+        check(
+            &["edit_mix"],
+            &[
+                ("Blinding Lights - Radio Edit", "Blinding Lights"),
+                ("Levels (Radio Edit)", "Levels"),
+                ("November Rain - Single Version", "November Rain"),
+                ("Karma Police (Album Version)", "Karma Police"),
+                ("Strobe - Extended Mix", "Strobe"),
+                ("Opus (Original Mix)", "Opus"),
+                ("Hey Jude - Mono", "Hey Jude"),
+                ("God Only Knows (Stereo Mix)", "God Only Knows"),
+                ("Sandstorm - Edit", "Sandstorm"),
+                ("Sweet Child O' Mine - Single Edit", "Sweet Child O' Mine"),
+                ("Superstition (Full-Length Version)", "Superstition"),
+                ("Where Is My Mind (Clean Edit)", "Where Is My Mind"),
+                // Remixes and titles containing the words are kept
+                ("Bangarang - Skrillex Remix", "Bangarang - Skrillex Remix"),
+                ("Titanium (David Guetta Remix)", "Titanium (David Guetta Remix)"),
+                ("Edit the World", "Edit the World"),
+                ("Mono No Aware", "Mono No Aware"),
+                ("Stereo Hearts", "Stereo Hearts"),
+                ("Self-Edit", "Self-Edit"),
+                ("Song (Re-Edit)", "Song (Re-Edit)"),
+                ("Mono-Stereo Blues", "Mono-Stereo Blues"),
+                ("Tune - Edit of the Year", "Tune - Edit of the Year"),
+                ("Song - Mono Blues", "Song - Mono Blues"),
+                ("Levels - Radio Edit [Explicit]", "Levels [Explicit]"),
+            ],
+        );
+    }
+
+    #[test]
+    fn edition() {
+        check(
+            &["edition"],
+            &[
+                ("All Too Well (Taylor's Version)", "All Too Well"),
+                ("All Too Well (10 Minute Version) (Taylor’s Version)", "All Too Well (10 Minute Version)"),
+                ("Mr. Perfectly Fine (Taylor's Version) [From The Vault]", "Mr. Perfectly Fine"),
+                ("Yellow (Deluxe Edition)", "Yellow"),
+                ("Song (Super Deluxe)", "Song"),
+                ("Clocks (10th Anniversary Edition)", "Clocks"),
+                ("Smile (Expanded Edition)", "Smile"),
+                ("Track [Bonus Track]", "Track"),
+                ("Track (Bonus)", "Track"),
+                ("Unwell - Bonus Track", "Unwell"),
+                ("Fix You - Deluxe Edition", "Fix You"),
+                ("Kagerou (Japan Edition)", "Kagerou"),
+                ("Number One (Collector's Edition)", "Number One"),
+                // Not edition tags
+                ("Special", "Special"),
+                ("Deluxe", "Deluxe"),
+                ("Limited Edition Love", "Limited Edition Love"),
+                ("Song - Special Edition Blues", "Song - Special Edition Blues"),
+                ("(Taylor Swift) Song", "(Taylor Swift) Song"),
+            ],
+        );
+    }
+
+    #[test]
+    fn explicit() {
+        check(
+            &["explicit"],
+            &[
+                ("HUMBLE. [Explicit]", "HUMBLE."),
+                ("Lose Yourself (Explicit Version)", "Lose Yourself"),
+                ("Still D.R.E. (Clean)", "Still D.R.E."),
+                ("Gangsta's Paradise (Dirty)", "Gangsta's Paradise"),
+                ("Money Trees - Explicit", "Money Trees"),
+                // Not explicit tags
+                ("Explicit Content", "Explicit Content"),
+                ("Clean", "Clean"),
+                ("Come Clean", "Come Clean"),
+            ],
+        );
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        check(
+            &["collapse_whitespace"],
+            &[("  Song   Title \t(Live) ", "Song Title (Live)"), ("Already clean", "Already clean")],
+        );
+    }
+
+    #[test]
+    fn full_pipeline_real_world_titles() {
+        check(
+            ALL,
+            &[
+                ("Bohemian Rhapsody - Remastered 2011", "Bohemian Rhapsody"),
+                ("Song - Remastered 2011 (Live at Wembley) [Explicit]", "Song"),
+                ("Blinding Lights - Radio Edit", "Blinding Lights"),
+                ("All Too Well (10 Minute Version) (Taylor's Version) [From The Vault]", "All Too Well (10 Minute Version)"),
+                ("Don't Stop Me Now - Remastered 2011 (feat. Someone)", "Don't Stop Me Now"),
+                ("Heroes - 2017 Remaster", "Heroes"),
+                ("Yellow (Deluxe Edition) [Explicit]", "Yellow"),
+                ("Levels (Radio Edit) [Explicit]", "Levels"),
+                ("Comfortably Numb - Live at Earls Court 1980", "Comfortably Numb"),
+                ("Hey Jude - Mono / Remastered 2015", "Hey Jude"),
+                ("First Love", "First Love"),
+                ("봄날 (Spring Day)", "봄날 (Spring Day)"),
+                ("Live Forever - Remastered", "Live Forever"),
+                ("Bangarang (feat. Sirah) - Skrillex Remix", "Bangarang (feat. Sirah) - Skrillex Remix"),
+            ],
+        );
+    }
+
+    #[test]
+    fn disabled_rules_are_skipped() {
+        let specs = vec![
+            RuleSpec { name: "remaster".into(), enabled: false, pattern: None, replace: None },
+            RuleSpec { name: "explicit".into(), enabled: true, pattern: None, replace: None },
+        ];
+        let cleanup = Cleanup::from_specs(&specs).unwrap();
+        assert_eq!(cleanup.apply("Heroes - 2017 Remaster [Explicit]"), "Heroes - 2017 Remaster");
+        let trace = cleanup.trace("Heroes - 2017 Remaster [Explicit]");
+        assert_eq!(trace[0].1, None);
+        assert_eq!(trace[1].1.as_deref(), Some("Heroes - 2017 Remaster"));
+    }
+
+    #[test]
+    fn invalid_and_unknown_rules_are_all_reported() {
+        let specs = vec![
+            RuleSpec { name: "broken".into(), enabled: true, pattern: Some("(unclosed".into()), replace: None },
+            RuleSpec { name: "nope".into(), enabled: true, pattern: None, replace: None },
+        ];
+        let errors = Cleanup::from_specs(&specs).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("'broken': invalid regex"));
+        assert!(errors[1].contains("'nope' is not built in"));
+    }
+
+    #[test]
+    fn custom_rule_with_replacement() {
+        let specs = vec![RuleSpec {
+            name: "amp".into(),
+            enabled: true,
+            pattern: Some("/ & /".into()),
+            replace: Some(" and ".into()),
+        }];
+        assert_eq!(Cleanup::from_specs(&specs).unwrap().apply("Simon & Garfunkel"), "Simon and Garfunkel");
+    }
+
+    #[test]
+    fn featured_artists_are_extracted_and_split() {
+        assert_eq!(
+            extract_featured("One More Time (feat. Romanthony)", "Daft Punk"),
+            ("One More Time".to_string(), vec!["Romanthony".to_string()])
+        );
+        assert_eq!(
+            extract_featured("Song (feat. A, B & C) - Remastered 2011", "X"),
+            ("Song - Remastered 2011".to_string(), vec!["A".to_string(), "B".to_string(), "C".to_string()])
+        );
+        assert_eq!(
+            extract_featured("Old Town Road - feat. Billy Ray Cyrus", "Lil Nas X"),
+            ("Old Town Road".to_string(), vec!["Billy Ray Cyrus".to_string()])
+        );
+        assert_eq!(
+            extract_featured("Under Pressure - with David Bowie", "Queen"),
+            ("Under Pressure".to_string(), vec!["David Bowie".to_string()])
+        );
+        // A bracketed "with" describes the recording, not a guest
+        assert_eq!(extract_featured("Stand by Me (With Strings)", "Ben E. King"), ("Stand by Me (With Strings)".to_string(), vec![]));
+        assert_eq!(extract_featured("Stay [with Justin Bieber]", "The Kid LAROI").1, Vec::<String>::new());
+        assert_eq!(extract_featured("Featherweight", "X"), ("Featherweight".to_string(), vec![]));
+        // The main artist credited again is not a featured artist
+        assert_eq!(extract_featured("Song (feat. Main & Guest)", "main").1, vec!["Guest".to_string()]);
+    }
+
+    #[test]
+    fn artist_credits_are_normalised() {
+        assert_eq!(split_artists("A, B & C"), vec!["A", "B", "C"]);
+        assert_eq!(split_artists("A &B,  C ; a"), vec!["A", "B", "C"]);
+        assert_eq!(split_artists("Florence and the Machine"), vec!["Florence and the Machine"]);
+        assert_eq!(split_artists("Guest ft. Other"), vec!["Guest", "Other"]);
+    }
+
+    #[test]
+    fn display_width_and_truncation() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("宇多田ヒカル"), 12);
+        assert_eq!(display_width("👩‍👩‍👧"), 2);
+        assert_eq!(display_width("Jóga"), 4);
+        assert_eq!(truncate_to_width("宇多田ヒカル", 7), "宇多田…");
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(pad_to_width("宇多", 6, Align::Right), "  宇多");
+        assert_eq!(take_chars("e\u{301}e\u{301}", 3), "e\u{301}");
+    }
+
+    #[test]
+    fn romanize_scripts() {
+        let cases = [
+            ("Кино — Группа крови", "Kino — Gruppa krovi"),
+            ("ДДТ", "DDT"),
+            ("Щедрик", "Shchedrik"),
+            ("Μάνος Χατζιδάκις", "Manos Chatzidakis"),
+            ("Μουσική", "Mousiki"),
+            ("ΟΥΡΑΝΟΣ", "OURANOS"),
+            ("Σπύρος", "Spyros"),
+            ("きゃりーぱみゅぱみゅ", "Kyariipamyupamyu"),
+            ("マッチャ", "Matcha"),
+            ("ティーンエイジャー", "Tiin'eijaa"),
+            ("フォルテ、「東京」", "Forute, \"東京\""),
+            ("봄날 (Spring Day)", "Bomnal (Spring Day)"),
+            ("Jóga", "Jóga"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(romanize(input), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn romanized_names_match_whole_names_only() {
+        // The only test that registers overrides: the list is global and set once.
+        set_romanized_names(vec![("Кино".to_string(), "KINO".to_string()), ("椎名林檎".to_string(), "Sheena Ringo".to_string())]);
+        assert_eq!(romanize_name("Кино"), "KINO");
+        assert_eq!(romanize_name(" кино "), "KINO");
+        assert_eq!(romanize_name("椎名林檎"), "Sheena Ringo");
+        // Part of a longer name or a title is transliterated, not replaced
+        assert_eq!(romanize_name("Кинотеатр"), "Kinoteatr");
+        assert_eq!(romanize_name("Кино и я"), "Kino i ya");
+        assert_eq!(romanize("Кино"), "Kino");
+    }
 }
@@ -30,9 +30,11 @@ pub fn select_ordered_with_cursive(items: Vec<String>) -> anyhow::Result<Vec<usi
     let mut siv = Cursive::default();
 
     // Apply a custom theme: black background with white text for best contrast.
-    let mut theme = Theme::default();
-    theme.shadow = false;
-    theme.borders = BorderStyle::Simple;
+    let mut theme = Theme {
+        shadow: false,
+        borders: BorderStyle::Simple,
+        ..Theme::default()
+    };
     theme.palette[PaletteColor::Background] = Color::Rgb(0, 0, 0);
     theme.palette[PaletteColor::View] = Color::Rgb(0, 0, 0);
     // Primary text colors set to white
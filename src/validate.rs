@@ -0,0 +1,496 @@
+use clap::ValueEnum;
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+
+use crate::cli::{ConflictPolicy, DiscordTarget, FitStrategy, OutputFormat, Period};
+use crate::text::builtin_rule_names;
+
+/// A problem found in the config file. The file still loads; the offending value is ignored.
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    /// Byte offset and length of the offending node or value in the source
+    pub offset: usize,
+    pub len: usize,
+    pub message: String,
+    pub help: Option<String>,
+}
+
+/// What a config key expects. Keep in sync with the `match` in `config::load_config`.
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    Int,
+    Bool,
+    /// One or more strings (`discord_guild_ids "1" "2"`)
+    Strings,
+    /// One of the names returned by the function
+    Enum(fn() -> Vec<String>),
+    /// Comma-separated names (`bio_fit "drop,truncate"`)
+    EnumList(fn() -> Vec<String>),
+    /// A block whose children are themselves keys
+    Block(&'static [(&'static str, Kind)]),
+    Cleanup,
+    Aliases,
+    Filters,
+    RomanizedNames,
+    Preset,
+    /// `profile "name" { ... }`: any top-level key except another profile
+    Profile,
+}
+
+fn names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants().iter().filter_map(|v| v.to_possible_value().map(|p| p.get_name().to_string())).collect()
+}
+
+const BOT_KEYS: &[(&str, Kind)] = &[("token", Kind::Str), ("channel_id", Kind::Str), ("post", Kind::Bool), ("about", Kind::Bool)];
+const PRESET_KEYS: &[(&str, Kind)] =
+    &[("format", Kind::Str), ("join", Kind::Str), ("prefix", Kind::Str), ("suffix", Kind::Str), ("list_template", Kind::Str)];
+
+const KEYS: &[(&str, Kind)] = &[
+    ("username", Kind::Str),
+    ("api_key", Kind::Str),
+    ("api_key_file", Kind::Str),
+    ("api_key_command", Kind::Str),
+    ("period", Kind::Enum(names::<Period>)),
+    ("limit", Kind::Int),
+    ("select", Kind::Int),
+    ("format", Kind::Str),
+    ("join", Kind::Str),
+    ("prefix", Kind::Str),
+    ("suffix", Kind::Str),
+    ("list_template", Kind::Str),
+    ("list_template_file", Kind::Str),
+    ("discord_markdown", Kind::Bool),
+    ("preset", Kind::Preset),
+    ("default_preset", Kind::Str),
+    ("strip_feat", Kind::Bool),
+    ("strip_feat_regex", Kind::Str),
+    ("cleanup", Kind::Cleanup),
+    ("aliases", Kind::Aliases),
+    ("filters", Kind::Filters),
+    ("move_feat", Kind::Bool),
+    ("merge", Kind::Bool),
+    ("transliterate", Kind::Bool),
+    ("romanized_names", Kind::RomanizedNames),
+    ("featured_separator", Kind::Str),
+    ("copy", Kind::Bool),
+    ("output_format", Kind::Enum(names::<OutputFormat>)),
+    ("table", Kind::Bool),
+    ("discord_token", Kind::Str),
+    ("discord_token_file", Kind::Str),
+    ("discord_token_command", Kind::Str),
+    ("discord_bio_regex", Kind::Str),
+    ("discord_bio_limit", Kind::Int),
+    ("bio_fit", Kind::EnumList(names::<FitStrategy>)),
+    ("fallback_format", Kind::Str),
+    ("update_discord", Kind::Bool),
+    ("discord_dry_run", Kind::Bool),
+    ("discord_confirm", Kind::Bool),
+    ("discord_on_conflict", Kind::Enum(names::<ConflictPolicy>)),
+    ("discord_targets", Kind::EnumList(names::<DiscordTarget>)),
+    ("status_format", Kind::Str),
+    ("status_emoji", Kind::Str),
+    ("status_expires", Kind::Str),
+    ("pronouns_format", Kind::Str),
+    ("discord_guild_ids", Kind::Strings),
+    ("webhook", Kind::Bool),
+    ("webhook_url", Kind::Str),
+    ("webhook_embed", Kind::Bool),
+    ("webhook_username", Kind::Str),
+    ("discord_bot", Kind::Block(BOT_KEYS)),
+    ("presence_client_id", Kind::Str),
+    ("presence_details", Kind::Str),
+    ("presence_state", Kind::Str),
+    ("presence_interval", Kind::Int),
+    ("debug", Kind::Bool),
+    ("profile", Kind::Profile),
+];
+
+/// Check a config file's contents: unknown keys (with "did you mean" suggestions), values of the wrong
+/// type and invalid enum values. KDL syntax errors are reported by the parser instead, so a document that
+/// doesn't parse yields no issues here.
+pub fn validate_config(source: &str) -> Vec<ConfigIssue> {
+    let Ok(doc) = source.parse::<KdlDocument>() else { return Vec::new() };
+    let mut issues = Vec::new();
+    let mut nodes: Vec<&KdlNode> = Vec::new();
+    match doc.get("topsongs") {
+        Some(root) => {
+            // Profiles may also sit next to the topsongs block
+            let (profiles, outside): (Vec<&KdlNode>, Vec<&KdlNode>) =
+                doc.nodes().iter().filter(|n| n.name().value() != "topsongs").partition(|n| n.name().value() == "profile");
+            for n in outside {
+                issues.push(issue(
+                    n.span().offset(),
+                    n.name().span().len(),
+                    format!("'{}' is outside the topsongs block and is ignored", n.name().value()),
+                    Some("move it inside `topsongs { ... }`".to_string()),
+                ));
+            }
+            nodes.extend(root.children().map(|c| c.nodes()).unwrap_or_default());
+            nodes.extend(profiles);
+        }
+        None => nodes.extend(doc.nodes()),
+    }
+    check_keys(&nodes, KEYS, "", &mut issues);
+
+    let mut seen: Vec<&str> = Vec::new();
+    for n in nodes.iter().filter(|n| n.name().value() == "profile") {
+        if let Some(name) = args(n).first().and_then(|e| e.value().as_string()) {
+            if seen.contains(&name) {
+                issues.push(node_issue(n, format!("profile '{}' is defined more than once; only the last one is used", name), None));
+            }
+            seen.push(name);
+        }
+    }
+    issues.sort_by_key(|i| i.offset);
+    issues
+}
+
+fn issue(offset: usize, len: usize, message: String, help: Option<String>) -> ConfigIssue {
+    ConfigIssue { offset, len: len.max(1), message, help }
+}
+
+fn node_issue(n: &KdlNode, message: String, help: Option<String>) -> ConfigIssue {
+    issue(n.name().span().offset(), n.name().span().len(), message, help)
+}
+
+fn entry_issue(e: &KdlEntry, message: String, help: Option<String>) -> ConfigIssue {
+    issue(e.span().offset(), e.span().len(), message, help)
+}
+
+fn check_keys(nodes: &[&KdlNode], keys: &[(&str, Kind)], parent: &str, issues: &mut Vec<ConfigIssue>) {
+    for n in nodes {
+        let name = n.name().value();
+        match keys.iter().find(|(k, _)| *k == name) {
+            Some((_, kind)) => check_node(n, *kind, &format!("{}{}", parent, name), issues),
+            None => {
+                let help = did_you_mean(name, keys.iter().map(|(k, _)| *k)).map(|s| format!("did you mean '{}'?", s));
+                let place = if parent.is_empty() { String::new() } else { format!(" in {}", parent.trim_end_matches('.')) };
+                issues.push(node_issue(n, format!("unknown key '{}'{}", name, place), help));
+            }
+        }
+    }
+}
+
+fn args(n: &KdlNode) -> Vec<&KdlEntry> {
+    n.entries().iter().filter(|e| e.name().is_none()).collect()
+}
+
+fn type_name(v: &KdlValue) -> &'static str {
+    match v {
+        KdlValue::String(_) => "a string",
+        KdlValue::Integer(_) => "a number",
+        KdlValue::Float(_) => "a decimal number",
+        KdlValue::Bool(_) => "a boolean",
+        KdlValue::Null => "#null",
+    }
+}
+
+/// Check that `n` has exactly one value and return it, reporting a missing value or extra ones.
+fn single_value<'a>(n: &'a KdlNode, key: &str, expected: &str, issues: &mut Vec<ConfigIssue>) -> Option<&'a KdlEntry> {
+    let values = args(n);
+    match values.as_slice() {
+        [] => {
+            issues.push(node_issue(n, format!("'{}' has no value (expected {})", key, expected), None));
+            None
+        }
+        [first, rest @ ..] => {
+            for extra in rest {
+                issues.push(entry_issue(extra, format!("extra value for '{}' is ignored", key), None));
+            }
+            Some(first)
+        }
+    }
+}
+
+fn check_node(n: &KdlNode, kind: Kind, key: &str, issues: &mut Vec<ConfigIssue>) {
+    for e in n.entries() {
+        if let Some(prop) = e.name() {
+            issues.push(entry_issue(e, format!("'{}' takes no properties; '{}' is ignored", key, prop.value()), None));
+        }
+    }
+    // `key` is the path used in messages (`profile.limit`); help shows what to write, so it uses the bare name
+    let name = n.name().value();
+    match kind {
+        Kind::Str => {
+            if let Some(e) = single_value(n, key, "a string", issues)
+                && !e.value().is_string()
+            {
+                let help = Some(format!("quote it: {} \"{}\"", name, e.value()));
+                issues.push(entry_issue(e, format!("'{}' expects a string, found {}", key, type_name(e.value())), help));
+            }
+        }
+        Kind::Int => {
+            if let Some(e) = single_value(n, key, "a number", issues) {
+                match e.value() {
+                    KdlValue::Integer(i) if *i >= 0 && u32::try_from(*i).is_ok() => {}
+                    KdlValue::Integer(_) => issues.push(entry_issue(e, format!("'{}' must be a whole number from 0 to {}", key, u32::MAX), None)),
+                    KdlValue::String(s) if s.trim().parse::<u32>().is_ok() => issues.push(entry_issue(
+                        e,
+                        format!("'{}' expects a number, found a string", key),
+                        Some(format!("remove the quotes: {} {}", name, s.trim())),
+                    )),
+                    v => issues.push(entry_issue(e, format!("'{}' expects a number, found {}", key, type_name(v)), None)),
+                }
+            }
+        }
+        Kind::Bool => {
+            if let Some(e) = single_value(n, key, "#true or #false", issues) {
+                match e.value() {
+                    KdlValue::Bool(_) => {}
+                    v => {
+                        let help = match v.as_string().map(|s| s.trim().to_ascii_lowercase()) {
+                            Some(s) if s == "true" || s == "false" => Some(format!("write {} #{}", name, s)),
+                            _ => Some("booleans are written #true or #false".to_string()),
+                        };
+                        issues.push(entry_issue(e, format!("'{}' expects #true or #false, found {}", key, type_name(v)), help));
+                    }
+                }
+            }
+        }
+        Kind::Strings => {
+            let values = args(n);
+            if values.is_empty() {
+                issues.push(node_issue(n, format!("'{}' has no values (expected one or more strings)", key), None));
+            }
+            for e in values.into_iter().filter(|e| !e.value().is_string()) {
+                issues.push(entry_issue(e, format!("'{}' expects strings, found {}", key, type_name(e.value())), None));
+            }
+        }
+        Kind::Enum(allowed) => {
+            let allowed = allowed();
+            if let Some(e) = single_value(n, key, "a string", issues) {
+                match e.value().as_string() {
+                    Some(v) if allowed.iter().any(|a| a.eq_ignore_ascii_case(v.trim())) => {}
+                    Some(v) => issues.push(invalid_name(e, key, v, &allowed)),
+                    None => issues.push(entry_issue(e, format!("'{}' expects a string, found {}", key, type_name(e.value())), None)),
+                }
+            }
+        }
+        Kind::EnumList(allowed) => {
+            let allowed = allowed();
+            if let Some(e) = single_value(n, key, "a comma-separated string", issues) {
+                match e.value().as_string() {
+                    Some(v) => {
+                        for item in v.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+                            if !allowed.iter().any(|a| a.eq_ignore_ascii_case(item)) {
+                                issues.push(invalid_name(e, key, item, &allowed));
+                            }
+                        }
+                    }
+                    None => issues.push(entry_issue(e, format!("'{}' expects a string, found {}", key, type_name(e.value())), None)),
+                }
+            }
+        }
+        Kind::Block(keys) => match n.children() {
+            Some(children) => check_keys(&children.nodes().iter().collect::<Vec<_>>(), keys, &format!("{}.", key), issues),
+            None => issues.push(node_issue(n, format!("'{}' expects a block: {} {{ ... }}", key, name), None)),
+        },
+        Kind::Preset => {
+            match args(n).first() {
+                Some(e) if e.value().is_string() => {}
+                _ => issues.push(node_issue(n, "preset needs a name: preset \"name\" { ... }".to_string(), None)),
+            }
+            if let Some(children) = n.children() {
+                check_keys(&children.nodes().iter().collect::<Vec<_>>(), PRESET_KEYS, "preset.", issues);
+            }
+        }
+        Kind::Profile => {
+            if key != "profile" {
+                issues.push(node_issue(n, "profiles can't be nested; this one is ignored".to_string(), None));
+                return;
+            }
+            match args(n).first() {
+                Some(e) if e.value().is_string() => {}
+                _ => issues.push(node_issue(n, "profile needs a name: profile \"name\" { ... }".to_string(), None)),
+            }
+            match n.children() {
+                Some(children) => check_keys(&children.nodes().iter().collect::<Vec<_>>(), KEYS, "profile.", issues),
+                None => issues.push(node_issue(n, "'profile' expects a block: profile \"name\" { ... }".to_string(), None)),
+            }
+        }
+        Kind::Cleanup => each_child(n, key, issues, check_cleanup_rule),
+        Kind::Aliases => each_child(n, key, issues, |c, issues| {
+            if expect_name(c, &["artist", "track"], "aliases", issues) {
+                expect_strings(c, 2, "aliases need a pattern and a replacement, e.g. artist \"beatles\" \"The Beatles\"", issues);
+            }
+        }),
+        Kind::Filters => each_child(n, key, issues, |c, issues| {
+            if expect_name(c, &["include", "exclude"], "filters", issues)
+                && expect_strings(c, 2, "filters need a field and a pattern, e.g. exclude artist \"Rain Sounds\"", issues)
+                && let Some(field) = args(c).first()
+                && let Some(name) = field.value().as_string()
+                && name != "artist"
+                && name != "track"
+            {
+                if matches!(name, "tag" | "tags") {
+                    issues.push(entry_issue(
+                        field,
+                        "tag filters are not supported".to_string(),
+                        Some("Last.fm's top tracks carry no tags; filter by artist or track name instead".to_string()),
+                    ));
+                } else {
+                    issues.push(invalid_name(field, c.name().value(), name, &["artist".to_string(), "track".to_string()]));
+                }
+            }
+        }),
+        Kind::RomanizedNames => each_child(n, key, issues, |c, issues| {
+            expect_strings(c, 1, "write the original name followed by the romanised one, e.g. \"宇多田ヒカル\" \"Hikaru Utada\"", issues);
+        }),
+    }
+}
+
+fn each_child(n: &KdlNode, key: &str, issues: &mut Vec<ConfigIssue>, mut check: impl FnMut(&KdlNode, &mut Vec<ConfigIssue>)) {
+    match n.children() {
+        Some(children) => children.nodes().iter().for_each(|c| check(c, issues)),
+        None => issues.push(node_issue(n, format!("'{}' expects a block: {} {{ ... }}", key, n.name().value()), None)),
+    }
+}
+
+fn expect_name(c: &KdlNode, allowed: &[&str], block: &str, issues: &mut Vec<ConfigIssue>) -> bool {
+    let name = c.name().value();
+    if allowed.contains(&name) {
+        return true;
+    }
+    let help = did_you_mean(name, allowed.iter().copied())
+        .map(|s| format!("did you mean '{}'?", s))
+        .or_else(|| Some(format!("expected {}", allowed.join(" or "))));
+    issues.push(node_issue(c, format!("unknown entry '{}' in {}", name, block), help));
+    false
+}
+
+fn expect_strings(c: &KdlNode, count: usize, help: &str, issues: &mut Vec<ConfigIssue>) -> bool {
+    let values = args(c);
+    if values.len() != count || values.iter().any(|e| !e.value().is_string()) {
+        issues.push(node_issue(c, format!("'{}' expects {} string value{}", c.name().value(), count, if count == 1 { "" } else { "s" }), Some(help.to_string())));
+        return false;
+    }
+    true
+}
+
+fn check_cleanup_rule(c: &KdlNode, issues: &mut Vec<ConfigIssue>) {
+    if !expect_name(c, &["rule"], "cleanup", issues) {
+        return;
+    }
+    let Some((name_entry, name)) = args(c).first().and_then(|e| e.value().as_string().map(|s| (*e, s))) else {
+        issues.push(node_issue(c, "rule needs a name: rule \"feat\"".to_string(), None));
+        return;
+    };
+    let mut has_pattern = false;
+    for e in c.entries() {
+        let Some(prop) = e.name() else { continue };
+        let ok = match prop.value() {
+            "enabled" => e.value().is_bool(),
+            "pattern" => {
+                has_pattern = true;
+                e.value().is_string()
+            }
+            "replace" => e.value().is_string(),
+            other => {
+                let help = did_you_mean(other, ["enabled", "pattern", "replace"]).map(|s| format!("did you mean '{}'?", s));
+                issues.push(entry_issue(e, format!("unknown rule property '{}'", other), help));
+                continue;
+            }
+        };
+        if !ok {
+            let expected = if prop.value() == "enabled" { "#true or #false" } else { "a string" };
+            issues.push(entry_issue(e, format!("rule property '{}' expects {}, found {}", prop.value(), expected, type_name(e.value())), None));
+        }
+    }
+    let builtins = builtin_rule_names();
+    if !has_pattern && !builtins.contains(&name) {
+        let help = did_you_mean(name, builtins.iter().copied())
+            .map(|s| format!("did you mean '{}'?", s))
+            .or_else(|| Some(format!("give it a pattern= or use a built-in rule: {}", builtins.join(", "))));
+        issues.push(entry_issue(name_entry, format!("cleanup rule '{}' is not built in and has no pattern", name), help));
+    }
+}
+
+fn invalid_name(e: &KdlEntry, key: &str, value: &str, allowed: &[String]) -> ConfigIssue {
+    let help = did_you_mean(value, allowed.iter().map(String::as_str)).map(|s| format!("did you mean '{}'?", s));
+    entry_issue(e, format!("invalid value '{}' for '{}' (expected {})", value, key, allowed.join(" | ")), help)
+}
+
+/// The closest candidate within a small edit distance, compared case-insensitively.
+fn did_you_mean<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let word = word.to_lowercase();
+    let max = (word.chars().count() / 3).clamp(1, 3);
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(&word, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= max)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb { prev } else { 1 + prev.min(row[j]).min(current) };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        validate_config(source).into_iter().map(|i| format!("{} ({})", i.message, i.help.unwrap_or_default())).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() {
+        let source = "topsongs {\n  username \"u\"\n  limit 5\n  period \"7day\"\n  strip_feat #true\n  bio_fit \"drop, truncate\"\n  discord_bot { channel_id \"1\"; post #true }\n  cleanup { rule \"feat\" enabled=#false; rule \"mine\" pattern=\"x\" replace=\"\" }\n  filters { exclude artist \"A\" }\n  aliases { track \"a\" \"b\" }\n  preset \"p\" { format \"{artist}\" }\n}\n";
+        assert!(messages(source).is_empty(), "{:?}", messages(source));
+    }
+
+    #[test]
+    fn reports_unknown_keys_and_bad_values() {
+        let source = "topsongs {\n  limt 5\n  limit \"10\"\n  period \"weekly\"\n  copy \"true\"\n}\n";
+        let issues = validate_config(source);
+        assert_eq!(
+            messages(source),
+            [
+                "unknown key 'limt' (did you mean 'limit'?)",
+                "'limit' expects a number, found a string (remove the quotes: limit 10)",
+                "invalid value 'weekly' for 'period' (expected overall | 7day | 1month | 3month | 6month | 12month) ()",
+                "'copy' expects #true or #false, found a string (write copy #true)",
+            ]
+        );
+        assert_eq!(&source[issues[1].offset..issues[1].offset + issues[1].len], "\"10\"");
+    }
+
+    #[test]
+    fn tag_filters_are_explained() {
+        assert_eq!(
+            messages("topsongs {\n  filters { exclude tag \"podcast\"; exclude artst \"A\" }\n}\n"),
+            [
+                "tag filters are not supported (Last.fm's top tracks carry no tags; filter by artist or track name instead)",
+                "invalid value 'artst' for 'exclude' (expected artist | track) (did you mean 'artist'?)",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_profiles_like_the_top_level() {
+        let source = "topsongs {\n  profile \"a\" { limt 5 }\n}\nprofile \"b\" { copy #true }\nprofile \"a\" { }\n";
+        assert_eq!(
+            messages(source),
+            ["unknown key 'limt' in profile (did you mean 'limit'?)", "profile 'a' is defined more than once; only the last one is used ()"]
+        );
+    }
+
+    #[test]
+    fn suggests_close_names_only() {
+        assert_eq!(did_you_mean("usrname", KEYS.iter().map(|(k, _)| *k)), Some("username"));
+        assert_eq!(did_you_mean("volume", KEYS.iter().map(|(k, _)| *k)), None);
+    }
+}
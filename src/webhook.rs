@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+
+use crate::http_template::send_template;
+use crate::lastfm::Track;
+use crate::render::render_template;
+
+/// Discord rejects message content longer than this.
+const CONTENT_LIMIT: usize = 2000;
+/// Discord allows at most this many fields per embed.
+const EMBED_FIELD_LIMIT: usize = 25;
+/// Last.fm's brand red, used as the embed accent color.
+const EMBED_COLOR: u32 = 0xD51007;
+
+pub struct WebhookMessage<'a> {
+    /// Chosen tracks, with title cleanup already applied
+    pub tracks: &'a [Track],
+    /// The fully rendered list (prefix + entries + suffix), used as plain message content
+    pub rendered: &'a str,
+    /// Send a rich embed instead of plain content
+    pub embed: bool,
+    /// Embed title, e.g. "Top tracks for alice (7day)"
+    pub title: &'a str,
+    /// Optional display name override for the webhook
+    pub username: Option<&'a str>,
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max { s.to_string() } else { crate::fit::truncate_with_ellipsis(s, max) }
+}
+
+/// Build the JSON body Discord expects for an "Execute Webhook" call.
+pub fn build_payload(msg: &WebhookMessage) -> Result<Value> {
+    let mut payload = if msg.embed {
+        let fields: Vec<Value> = msg
+            .tracks
+            .iter()
+            .take(EMBED_FIELD_LIMIT)
+            .enumerate()
+            .map(|(i, t)| {
+                let mut value = render_template("{artist} · {playcount} plays", t);
+                if !t.url.is_empty() {
+                    value.push_str(&format!("\n[Last.fm]({})", t.url));
+                }
+                json!({
+                    "name": truncate_chars(&format!("{}. {}", i + 1, t.name), 256),
+                    "value": truncate_chars(&value, 1024),
+                    "inline": false,
+                })
+            })
+            .collect();
+        json!({
+            "embeds": [{
+                "title": truncate_chars(msg.title, 256),
+                "color": EMBED_COLOR,
+                "fields": fields,
+            }]
+        })
+    } else {
+        let len = msg.rendered.chars().count();
+        if len > CONTENT_LIMIT {
+            return Err(anyhow!(
+                "Webhook message is {} characters, over Discord's limit of {}. Select fewer tracks or use --webhook-embed.",
+                len,
+                CONTENT_LIMIT
+            ));
+        }
+        json!({ "content": msg.rendered })
+    };
+    if let Some(name) = msg.username {
+        payload["username"] = json!(name);
+    }
+    // Never let track names ping @everyone or roles
+    payload["allowed_mentions"] = json!({ "parse": [] });
+    Ok(payload)
+}
+
+/// POST `payload` to the webhook using the discord_webhook.http template.
+pub async fn post_webhook(url: &str, payload: &Value, debug: bool) -> Result<()> {
+    let vars = [("WEBHOOK_URL", url.to_string()), ("WEBHOOK_PAYLOAD", payload.to_string())];
+    send_template("discord_webhook.http", &vars, debug).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_template::send_template_content;
+    use crate::lastfm::Artist;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn track(name: &str, artist: &str, url: &str) -> Track {
+        Track {
+            name: name.to_string(),
+            playcount: "42".to_string(),
+            artist: Artist { name: artist.to_string() },
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Accept one request, answer 204 like Discord does, and return the raw request head and the body.
+    async fn stand_in(listener: TcpListener) -> (String, String) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&raw).to_string();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if raw.len() >= end + 4 + length {
+                    socket.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+                    return (text[..end].to_string(), text[end + 4..end + 4 + length].to_string());
+                }
+            }
+            assert!(n > 0, "connection closed before the request was complete");
+        }
+    }
+
+    #[test]
+    fn plain_content_payload() {
+        let tracks = [track("One More Time", "Daft Punk", "")];
+        let msg = WebhookMessage { tracks: &tracks, rendered: "Top:\n- @everyone", embed: false, title: "", username: Some("TopSongs") };
+        let payload = build_payload(&msg).unwrap();
+        assert_eq!(payload, json!({ "content": "Top:\n- @everyone", "username": "TopSongs", "allowed_mentions": { "parse": [] } }));
+
+        let long = "x".repeat(CONTENT_LIMIT + 1);
+        let msg = WebhookMessage { rendered: &long, ..msg };
+        assert!(build_payload(&msg).is_err());
+    }
+
+    #[tokio::test]
+    async fn posts_embed_to_a_local_stand_in() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/webhooks/123/secret", listener.local_addr().unwrap());
+        let server = tokio::spawn(stand_in(listener));
+
+        let tracks = [track("One More Time", "Daft Punk", "https://www.last.fm/music/Daft+Punk/_/One+More+Time"), track("Heroes", "David Bowie", "")];
+        let msg = WebhookMessage { tracks: &tracks, rendered: "", embed: true, title: "Top tracks for alice (7day)", username: None };
+        let payload = build_payload(&msg).unwrap();
+        let vars = [("WEBHOOK_URL", url), ("WEBHOOK_PAYLOAD", payload.to_string())];
+        let response = send_template_content(include_str!("../http/discord_webhook.http"), &vars, false).await.unwrap();
+        assert_eq!(response.status(), 204);
+
+        let (head, body) = server.await.unwrap();
+        assert!(head.starts_with("POST /api/webhooks/123/secret HTTP/1.1"), "{}", head);
+        assert!(head.to_ascii_lowercase().contains("content-type: application/json"));
+        let sent: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(sent, payload);
+        let embed = &sent["embeds"][0];
+        assert_eq!(embed["title"], "Top tracks for alice (7day)");
+        assert_eq!(embed["fields"][0]["name"], "1. One More Time");
+        assert_eq!(embed["fields"][0]["value"], "Daft Punk · 42 plays\n[Last.fm](https://www.last.fm/music/Daft+Punk/_/One+More+Time)");
+        assert_eq!(embed["fields"][1]["value"], "David Bowie · 42 plays");
+        assert_eq!(sent["allowed_mentions"], json!({ "parse": [] }));
+    }
+
+    #[tokio::test]
+    async fn failed_post_does_not_leak_the_webhook_token() {
+        // Bind and drop a listener to get a local port that refuses connections.
+        let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let vars = [
+            ("WEBHOOK_URL", format!("http://{}/api/webhooks/123/very-secret-token", addr)),
+            ("WEBHOOK_PAYLOAD", json!({ "content": "hi" }).to_string()),
+        ];
+        let err = send_template_content(include_str!("../http/discord_webhook.http"), &vars, false).await.unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(!message.contains("very-secret-token"), "{}", message);
+    }
+}